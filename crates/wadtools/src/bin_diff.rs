@@ -0,0 +1,306 @@
+use crate::utils::BinHashtable;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use league_toolkit::meta::{BinProperty, BinTree, PropertyValueEnum};
+use serde::Serialize;
+use std::io::Cursor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BinChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A single added, removed or changed object or field of a property bin
+#[derive(Debug, Clone, Serialize)]
+pub struct BinChange {
+    pub kind: BinChangeKind,
+    /// Resolved entry path of the object
+    pub object: String,
+    /// Resolved class name of the object
+    pub class: String,
+    /// Dotted path of the changed field, `None` if the change affects the whole object
+    pub field: Option<String>,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+/// Parses both sides as property bins and compares their objects and fields.
+/// Returns `None` if either side is not a valid property bin.
+pub fn diff_bin_data(
+    old_data: &[u8],
+    new_data: &[u8],
+    hashtable: &BinHashtable,
+) -> Option<Vec<BinChange>> {
+    let old_tree = BinTree::from_reader(&mut Cursor::new(old_data))
+        .inspect_err(|error| tracing::warn!("failed to parse reference bin: {}", error))
+        .ok()?;
+    let new_tree = BinTree::from_reader(&mut Cursor::new(new_data))
+        .inspect_err(|error| tracing::warn!("failed to parse target bin: {}", error))
+        .ok()?;
+
+    Some(diff_bin_trees(&old_tree, &new_tree, hashtable))
+}
+
+pub fn diff_bin_trees(old: &BinTree, new: &BinTree, hashtable: &BinHashtable) -> Vec<BinChange> {
+    let mut changes = Vec::new();
+
+    for dependency in old
+        .dependencies
+        .iter()
+        .filter(|d| !new.dependencies.contains(d))
+    {
+        changes.push(dependency_change(BinChangeKind::Removed, dependency));
+    }
+    for dependency in new
+        .dependencies
+        .iter()
+        .filter(|d| !old.dependencies.contains(d))
+    {
+        changes.push(dependency_change(BinChangeKind::Added, dependency));
+    }
+
+    for (path_hash, old_object) in &old.objects {
+        let object = hashtable.resolve(*path_hash).to_string();
+        let class = hashtable.resolve(old_object.class_hash).to_string();
+
+        let Some(new_object) = new.objects.get(path_hash) else {
+            changes.push(BinChange {
+                kind: BinChangeKind::Removed,
+                object,
+                class,
+                field: None,
+                old_value: None,
+                new_value: None,
+            });
+            continue;
+        };
+
+        if old_object.class_hash != new_object.class_hash {
+            changes.push(BinChange {
+                kind: BinChangeKind::Changed,
+                object,
+                new_value: Some(hashtable.resolve(new_object.class_hash).to_string()),
+                old_value: Some(class.clone()),
+                class,
+                field: None,
+            });
+            continue;
+        }
+
+        let mut field_changes = Vec::new();
+        diff_properties(
+            "",
+            &old_object.properties,
+            &new_object.properties,
+            hashtable,
+            &mut field_changes,
+        );
+        changes.extend(
+            field_changes
+                .into_iter()
+                .map(|(kind, field, old_value, new_value)| BinChange {
+                    kind,
+                    object: object.clone(),
+                    class: class.clone(),
+                    field: Some(field),
+                    old_value,
+                    new_value,
+                }),
+        );
+    }
+
+    for (path_hash, new_object) in &new.objects {
+        if old.objects.contains_key(path_hash) {
+            continue;
+        }
+
+        changes.push(BinChange {
+            kind: BinChangeKind::Added,
+            object: hashtable.resolve(*path_hash).to_string(),
+            class: hashtable.resolve(new_object.class_hash).to_string(),
+            field: None,
+            old_value: None,
+            new_value: None,
+        });
+    }
+
+    changes
+}
+
+fn dependency_change(kind: BinChangeKind, dependency: &str) -> BinChange {
+    BinChange {
+        kind,
+        object: "(dependencies)".to_string(),
+        class: String::new(),
+        field: None,
+        old_value: (kind == BinChangeKind::Removed).then(|| dependency.to_string()),
+        new_value: (kind == BinChangeKind::Added).then(|| dependency.to_string()),
+    }
+}
+
+type FieldChange = (BinChangeKind, String, Option<String>, Option<String>);
+
+/// Compares two property sets, recursing into structs of the same class
+fn diff_properties(
+    prefix: &str,
+    old: &IndexMap<u32, BinProperty>,
+    new: &IndexMap<u32, BinProperty>,
+    hashtable: &BinHashtable,
+    changes: &mut Vec<FieldChange>,
+) {
+    let field_path = |name_hash: u32| {
+        let name = hashtable.resolve(name_hash);
+        if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", prefix, name)
+        }
+    };
+
+    for (name_hash, old_property) in old {
+        let field = field_path(*name_hash);
+        let Some(new_property) = new.get(name_hash) else {
+            changes.push((
+                BinChangeKind::Removed,
+                field,
+                Some(format_bin_value(&old_property.value, hashtable)),
+                None,
+            ));
+            continue;
+        };
+
+        if old_property.value == new_property.value {
+            continue;
+        }
+
+        match (&old_property.value, &new_property.value) {
+            (PropertyValueEnum::Struct(old_struct), PropertyValueEnum::Struct(new_struct))
+                if old_struct.class_hash == new_struct.class_hash =>
+            {
+                diff_properties(
+                    &field,
+                    &old_struct.properties,
+                    &new_struct.properties,
+                    hashtable,
+                    changes,
+                );
+            }
+            (PropertyValueEnum::Embedded(old_struct), PropertyValueEnum::Embedded(new_struct))
+                if old_struct.0.class_hash == new_struct.0.class_hash =>
+            {
+                diff_properties(
+                    &field,
+                    &old_struct.0.properties,
+                    &new_struct.0.properties,
+                    hashtable,
+                    changes,
+                );
+            }
+            (old_value, new_value) => changes.push((
+                BinChangeKind::Changed,
+                field,
+                Some(format_bin_value(old_value, hashtable)),
+                Some(format_bin_value(new_value, hashtable)),
+            )),
+        }
+    }
+
+    for (name_hash, new_property) in new {
+        if old.contains_key(name_hash) {
+            continue;
+        }
+
+        changes.push((
+            BinChangeKind::Added,
+            field_path(*name_hash),
+            None,
+            Some(format_bin_value(&new_property.value, hashtable)),
+        ));
+    }
+}
+
+/// Formats a property value on a single line, resolving hashes where possible
+pub fn format_bin_value(value: &PropertyValueEnum, hashtable: &BinHashtable) -> String {
+    match value {
+        PropertyValueEnum::None(_) => "null".to_string(),
+        PropertyValueEnum::Bool(v) => v.0.to_string(),
+        PropertyValueEnum::BitBool(v) => v.0.to_string(),
+        PropertyValueEnum::I8(v) => v.0.to_string(),
+        PropertyValueEnum::U8(v) => v.0.to_string(),
+        PropertyValueEnum::I16(v) => v.0.to_string(),
+        PropertyValueEnum::U16(v) => v.0.to_string(),
+        PropertyValueEnum::I32(v) => v.0.to_string(),
+        PropertyValueEnum::U32(v) => v.0.to_string(),
+        PropertyValueEnum::I64(v) => v.0.to_string(),
+        PropertyValueEnum::U64(v) => v.0.to_string(),
+        PropertyValueEnum::F32(v) => v.0.to_string(),
+        PropertyValueEnum::Vector2(v) => v.0.to_string(),
+        PropertyValueEnum::Vector3(v) => v.0.to_string(),
+        PropertyValueEnum::Vector4(v) => v.0.to_string(),
+        PropertyValueEnum::Matrix44(v) => v.0.to_string(),
+        PropertyValueEnum::Color(v) => {
+            format!("rgba({}, {}, {}, {})", v.0.r, v.0.g, v.0.b, v.0.a)
+        }
+        PropertyValueEnum::String(v) => format!("{:?}", v.0),
+        PropertyValueEnum::Hash(v) => format!("#{}", hashtable.resolve(v.0)),
+        PropertyValueEnum::ObjectLink(v) => format!("link {}", hashtable.resolve(v.0)),
+        PropertyValueEnum::WadChunkLink(v) => format!("chunk {:016x}", v.0),
+        PropertyValueEnum::Container(v) => format_bin_items(&v.items, hashtable),
+        PropertyValueEnum::UnorderedContainer(v) => format_bin_items(&v.0.items, hashtable),
+        PropertyValueEnum::Struct(v) => format_bin_struct(v.class_hash, &v.properties, hashtable),
+        PropertyValueEnum::Embedded(v) => {
+            format_bin_struct(v.0.class_hash, &v.0.properties, hashtable)
+        }
+        PropertyValueEnum::Optional(v) => match &v.value {
+            Some(value) => format_bin_value(value, hashtable),
+            None => "null".to_string(),
+        },
+        PropertyValueEnum::Map(v) => format!(
+            "{{{}}}",
+            v.entries
+                .iter()
+                .map(|(key, value)| format!(
+                    "{}: {}",
+                    format_bin_value(&key.0, hashtable),
+                    format_bin_value(value, hashtable)
+                ))
+                .join(", ")
+        ),
+    }
+}
+
+fn format_bin_items(items: &[PropertyValueEnum], hashtable: &BinHashtable) -> String {
+    format!(
+        "[{}]",
+        items
+            .iter()
+            .map(|item| format_bin_value(item, hashtable))
+            .join(", ")
+    )
+}
+
+fn format_bin_struct(
+    class_hash: u32,
+    properties: &IndexMap<u32, BinProperty>,
+    hashtable: &BinHashtable,
+) -> String {
+    if properties.is_empty() {
+        return format!("{} {{}}", hashtable.resolve(class_hash));
+    }
+
+    format!(
+        "{} {{ {} }}",
+        hashtable.resolve(class_hash),
+        properties
+            .iter()
+            .map(|(name_hash, property)| format!(
+                "{}: {}",
+                hashtable.resolve(*name_hash),
+                format_bin_value(&property.value, hashtable)
+            ))
+            .join(", ")
+    )
+}
@@ -0,0 +1,220 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use eyre::{eyre, Context};
+use flate2::read::GzDecoder;
+use league_toolkit::{
+    file::{LeagueFileKind, MAX_MAGIC_SIZE},
+    wad::{WadChunk, WadChunkCompression},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs, io,
+    io::{Read, Seek, SeekFrom},
+};
+use xxhash_rust::xxh64::xxh64;
+
+use crate::utils::{default_cache_dir, format_chunk_path_hash};
+
+const CHUNK_KINDS_VERSION: u32 = 1;
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// Identifies the kind of a chunk from its magic, decompressing only the head of its data
+pub fn identify_chunk_kind<TSource: Read + Seek>(
+    source: &mut TSource,
+    chunk: &WadChunk,
+) -> eyre::Result<LeagueFileKind> {
+    let head = read_chunk_head(source, chunk, MAX_MAGIC_SIZE)?;
+    Ok(LeagueFileKind::identify_from_bytes(&head))
+}
+
+/// Decompresses the first `len` bytes of the chunk (fewer if the chunk is smaller)
+fn read_chunk_head<TSource: Read + Seek>(
+    source: &mut TSource,
+    chunk: &WadChunk,
+    len: usize,
+) -> eyre::Result<Vec<u8>> {
+    let mut head = vec![0; len.min(chunk.uncompressed_size)];
+    source.seek(SeekFrom::Start(chunk.data_offset as u64))?;
+    let mut raw = (&mut *source).take(chunk.compressed_size as u64);
+
+    match chunk.compression_type {
+        WadChunkCompression::None => raw.read_exact(&mut head)?,
+        WadChunkCompression::GZip => GzDecoder::new(raw).read_exact(&mut head)?,
+        WadChunkCompression::Zstd => zstd::Decoder::new(raw)?.read_exact(&mut head)?,
+        WadChunkCompression::ZstdMulti => {
+            // Uncompressed data comes first, up to the first zstd frame
+            let mut prefix =
+                vec![0; (head.len() + ZSTD_MAGIC.len() - 1).min(chunk.compressed_size)];
+            raw.read_exact(&mut prefix)?;
+            let uncompressed_len = prefix
+                .windows(ZSTD_MAGIC.len())
+                .position(|window| window == ZSTD_MAGIC)
+                .unwrap_or(prefix.len())
+                .min(head.len());
+            head[..uncompressed_len].copy_from_slice(&prefix[..uncompressed_len]);
+
+            if uncompressed_len < head.len() {
+                source.seek(SeekFrom::Start(
+                    (chunk.data_offset + uncompressed_len) as u64,
+                ))?;
+                let frames = source.take((chunk.compressed_size - uncompressed_len) as u64);
+                zstd::Decoder::new(frames)?.read_exact(&mut head[uncompressed_len..])?;
+            }
+        }
+        WadChunkCompression::Satellite => {
+            return Err(eyre!("satellite chunks are not supported"));
+        }
+    }
+
+    Ok(head)
+}
+
+/// Kinds detected by earlier listings of a wad, stored in the cache directory. Entries are keyed
+/// by path hash and checksum, so chunks whose data changed are detected again.
+#[derive(Debug, Default)]
+pub struct ChunkKindCache {
+    /// Where the cache is stored, `None` when there is no cache directory
+    path: Option<Utf8PathBuf>,
+    /// Path hash -> (checksum, kind)
+    kinds: HashMap<u64, (u64, LeagueFileKind)>,
+    modified: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ChunkKindCacheFile {
+    version: u32,
+    chunks: Vec<ChunkKindCacheEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ChunkKindCacheEntry {
+    path_hash: String,
+    checksum: String,
+    kind: LeagueFileKind,
+}
+
+impl ChunkKindCache {
+    /// Loads the cache of the wad. A missing or unreadable cache is treated as empty, since it
+    /// can always be rebuilt.
+    pub fn load(wad_path: &Utf8Path) -> Self {
+        let Some(path) = Self::cache_path(wad_path) else {
+            return Self::default();
+        };
+
+        let kinds = match Self::read(&path) {
+            Ok(kinds) => kinds,
+            Err(error) => {
+                tracing::debug!("ignoring chunk kind cache {} ({:#})", path, error);
+                HashMap::new()
+            }
+        };
+
+        Self {
+            path: Some(path),
+            kinds,
+            modified: false,
+        }
+    }
+
+    /// One file per wad, named after the hash of its canonical path
+    fn cache_path(wad_path: &Utf8Path) -> Option<Utf8PathBuf> {
+        let wad_path = fs::canonicalize(wad_path.as_std_path()).ok()?;
+        let wad_path_hash = xxh64(wad_path.to_string_lossy().as_bytes(), 0);
+
+        Some(
+            default_cache_dir()?
+                .join("chunk_kinds")
+                .join(format!("{}.json", format_chunk_path_hash(wad_path_hash))),
+        )
+    }
+
+    fn read(path: &Utf8Path) -> eyre::Result<HashMap<u64, (u64, LeagueFileKind)>> {
+        let contents = match fs::read_to_string(path.as_std_path()) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(error) => return Err(error.into()),
+        };
+
+        let file: ChunkKindCacheFile = serde_json::from_str(&contents)?;
+        if file.version != CHUNK_KINDS_VERSION {
+            return Err(eyre!("unsupported version {}", file.version));
+        }
+
+        let mut kinds = HashMap::with_capacity(file.chunks.len());
+        for entry in file.chunks {
+            let path_hash = u64::from_str_radix(&entry.path_hash, 16)?;
+            let checksum = u64::from_str_radix(&entry.checksum, 16)?;
+            kinds.insert(path_hash, (checksum, entry.kind));
+        }
+
+        Ok(kinds)
+    }
+
+    pub fn get(&self, chunk: &WadChunk) -> Option<LeagueFileKind> {
+        self.kinds
+            .get(&chunk.path_hash)
+            .filter(|(checksum, _)| *checksum == chunk.checksum)
+            .map(|(_, kind)| *kind)
+    }
+
+    pub fn insert(&mut self, chunk: &WadChunk, kind: LeagueFileKind) {
+        self.kinds.insert(chunk.path_hash, (chunk.checksum, kind));
+        self.modified = true;
+    }
+
+    /// Returns the cached kind of the chunk, or detects and caches it
+    pub fn identify<TSource: Read + Seek>(
+        &mut self,
+        source: &mut TSource,
+        chunk: &WadChunk,
+    ) -> eyre::Result<LeagueFileKind> {
+        if let Some(kind) = self.get(chunk) {
+            return Ok(kind);
+        }
+
+        let kind = identify_chunk_kind(source, chunk).wrap_err(format!(
+            "failed to read chunk (chunk_hash: {})",
+            format_chunk_path_hash(chunk.path_hash)
+        ))?;
+        self.insert(chunk, kind);
+        Ok(kind)
+    }
+
+    /// Writes the cache if anything was added, dropping the entries of chunks that are not in
+    /// `present_chunks` anymore
+    pub fn save(&mut self, present_chunks: &HashMap<u64, WadChunk>) -> eyre::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.modified {
+            return Ok(());
+        }
+        self.kinds
+            .retain(|path_hash, _| present_chunks.contains_key(path_hash));
+
+        let mut chunks = self
+            .kinds
+            .iter()
+            .map(|(path_hash, (checksum, kind))| ChunkKindCacheEntry {
+                path_hash: format_chunk_path_hash(*path_hash),
+                checksum: format_chunk_path_hash(*checksum),
+                kind: *kind,
+            })
+            .collect::<Vec<_>>();
+        chunks.sort_by(|a, b| a.path_hash.cmp(&b.path_hash));
+
+        let file = ChunkKindCacheFile {
+            version: CHUNK_KINDS_VERSION,
+            chunks,
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent.as_std_path())?;
+        }
+        fs::write(path.as_std_path(), serde_json::to_string(&file)?)
+            .wrap_err(format!("failed to write chunk kind cache (path: {})", path))?;
+
+        self.modified = false;
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use std::io::{Read, Seek};
+
+use eyre::Context;
+use league_toolkit::{file::LeagueFileKind, wad::WadDecoder};
+use serde::Serialize;
+use similar::TextDiff;
+
+use crate::{
+    bin_diff::{diff_bin_data, BinChange},
+    utils::{BinHashtable, WadHashtable},
+};
+
+use super::ChunkDiff;
+
+/// Changes between the decompressed data of a modified chunk
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentDiff {
+    /// Offset of the first differing byte, `None` if the contents are identical
+    pub first_difference: Option<usize>,
+    /// Number of differing bytes, counting bytes past the end of the shorter side as changed
+    pub changed_bytes: usize,
+    pub size_delta: i64,
+    /// Unified diff, only present for text-like chunks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unified_diff: Option<String>,
+    /// Object and field changes, only present for property bin chunks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bin_changes: Option<Vec<BinChange>>,
+}
+
+impl ContentDiff {
+    pub fn compute(
+        old_data: &[u8],
+        new_data: &[u8],
+        path: &str,
+        bin_hashtable: &BinHashtable,
+    ) -> Self {
+        let common_len = old_data.len().min(new_data.len());
+        let length_delta = old_data.len().abs_diff(new_data.len());
+
+        let mismatches = (0..common_len).filter(|&i| old_data[i] != new_data[i]);
+        let first_difference = mismatches
+            .clone()
+            .next()
+            .or((length_delta > 0).then_some(common_len));
+        let changed_bytes = mismatches.count() + length_delta;
+
+        let unified_diff = match (as_text(old_data), as_text(new_data)) {
+            (Some(old_text), Some(new_text)) if first_difference.is_some() => Some(
+                TextDiff::from_lines(old_text, new_text)
+                    .unified_diff()
+                    .header(&format!("a/{}", path), &format!("b/{}", path))
+                    .to_string(),
+            ),
+            _ => None,
+        };
+
+        let bin_changes = match (
+            LeagueFileKind::identify_from_bytes(old_data),
+            LeagueFileKind::identify_from_bytes(new_data),
+        ) {
+            (
+                LeagueFileKind::PropertyBin | LeagueFileKind::PropertyBinOverride,
+                LeagueFileKind::PropertyBin | LeagueFileKind::PropertyBinOverride,
+            ) if first_difference.is_some() => diff_bin_data(old_data, new_data, bin_hashtable),
+            _ => None,
+        };
+
+        Self {
+            first_difference,
+            changed_bytes,
+            size_delta: new_data.len() as i64 - old_data.len() as i64,
+            unified_diff,
+            bin_changes,
+        }
+    }
+}
+
+/// Returns the data as text if it is a text-like chunk
+fn as_text(data: &[u8]) -> Option<&str> {
+    let is_text_kind = match LeagueFileKind::identify_from_bytes(data) {
+        LeagueFileKind::Svg | LeagueFileKind::StaticMeshAscii => true,
+        // Plain text files (json, lua, css, ...) have no magic
+        LeagueFileKind::Unknown => !data.contains(&0),
+        _ => false,
+    };
+
+    is_text_kind
+        .then(|| std::str::from_utf8(data).ok())
+        .flatten()
+}
+
+/// Decompresses both sides of every modified chunk and compares their contents
+pub fn collect_content_diffs<TRefSource, TTargetSource>(
+    diffs: &mut [ChunkDiff],
+    reference_decoder: &mut WadDecoder<TRefSource>,
+    target_decoder: &mut WadDecoder<TTargetSource>,
+    hashtable: &WadHashtable,
+    bin_hashtable: &BinHashtable,
+) -> eyre::Result<()>
+where
+    TRefSource: Read + Seek,
+    TTargetSource: Read + Seek,
+{
+    for diff in diffs.iter_mut() {
+        let ChunkDiff::Modified { old, new, content } = diff else {
+            continue;
+        };
+
+        let path = hashtable.resolve_path(old.path_hash);
+        let old_data = reference_decoder
+            .load_chunk_decompressed(old)
+            .wrap_err(format!(
+                "failed to decompress reference chunk (chunk_path: {})",
+                path
+            ))?;
+        let new_data = target_decoder
+            .load_chunk_decompressed(new)
+            .wrap_err(format!(
+                "failed to decompress target chunk (chunk_path: {})",
+                path
+            ))?;
+
+        *content = Some(ContentDiff::compute(
+            &old_data,
+            &new_data,
+            &path,
+            bin_hashtable,
+        ));
+    }
+
+    Ok(())
+}
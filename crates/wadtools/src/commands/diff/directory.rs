@@ -0,0 +1,227 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::File,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use eyre::Context;
+use league_toolkit::wad::{Wad, WadChunk};
+use rayon::prelude::*;
+use tracing_indicatif::span_ext::IndicatifSpanExt;
+use tracing_indicatif::style::ProgressStyle;
+use walkdir::WalkDir;
+
+use crate::utils::{is_wad_path, BinHashtable, WadHashtable};
+
+use super::{diff_wads, ChunkDiff, DiffEntry, DiffReport, WadsSummary};
+
+/// A chunk that is missing from one side of the diff, either because it was removed from
+/// (or added to) a wad present on both sides, or because its whole wad was removed (or added)
+struct UnpairedChunk<'a> {
+    wad: &'a str,
+    chunk: WadChunk,
+    /// Index of the chunk diff in its wad pair, `None` for chunks of added or removed wads
+    diff_index: Option<(usize, usize)>,
+}
+
+/// Diffs every wad in the reference directory against the wad with the same relative path in
+/// the target directory, and detects chunks that moved between wads
+pub fn diff_directories(
+    reference_dir: &Utf8Path,
+    target_dir: &Utf8Path,
+    hashtable: &WadHashtable,
+    bin_hashtable: Option<&BinHashtable>,
+) -> eyre::Result<DiffReport> {
+    let reference_wads = collect_wad_paths(reference_dir)?;
+    let target_wads = collect_wad_paths(target_dir)?;
+
+    let paired_wads = reference_wads
+        .keys()
+        .filter(|wad| target_wads.contains_key(*wad))
+        .collect::<Vec<_>>();
+    let removed_wads = reference_wads
+        .keys()
+        .filter(|wad| !target_wads.contains_key(*wad))
+        .collect::<Vec<_>>();
+    let added_wads = target_wads
+        .keys()
+        .filter(|wad| !reference_wads.contains_key(*wad))
+        .collect::<Vec<_>>();
+
+    tracing::info!(
+        "Comparing {} wads ({} added, {} removed)...",
+        paired_wads.len(),
+        added_wads.len(),
+        removed_wads.len()
+    );
+
+    let span = tracing::info_span!("diff", total = paired_wads.len());
+    span.pb_set_style(
+        &ProgressStyle::with_template("{wide_bar:40.cyan/blue} {pos}/{len} \n {spinner} {msg}")
+            .unwrap(),
+    );
+    span.pb_set_length(paired_wads.len() as u64);
+    span.pb_set_message("Diffing wads");
+    span.pb_set_finish_message("Diffing complete");
+
+    let pair_diffs = span.in_scope(|| {
+        paired_wads
+            .par_iter()
+            .map(|wad| {
+                let diffs = diff_wads(
+                    &reference_wads[*wad],
+                    &target_wads[*wad],
+                    hashtable,
+                    bin_hashtable,
+                )?;
+                span.pb_inc(1);
+                Ok((wad.as_str(), diffs))
+            })
+            .collect::<eyre::Result<Vec<_>>>()
+    })?;
+
+    // Chunks of added and removed wads are only needed to find moved chunks
+    let removed_wad_chunks = removed_wads
+        .par_iter()
+        .map(|wad| Ok((wad.as_str(), read_wad_chunks(&reference_wads[*wad])?)))
+        .collect::<eyre::Result<Vec<_>>>()?;
+    let added_wad_chunks = added_wads
+        .par_iter()
+        .map(|wad| Ok((wad.as_str(), read_wad_chunks(&target_wads[*wad])?)))
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    let mut removed_chunks = Vec::<UnpairedChunk>::new();
+    let mut new_chunks = Vec::<UnpairedChunk>::new();
+    for (pair_index, (wad, diffs)) in pair_diffs.iter().enumerate() {
+        for (diff_index, diff) in diffs.iter().enumerate() {
+            let (chunks, chunk) = match diff {
+                ChunkDiff::Removed(chunk) => (&mut removed_chunks, chunk),
+                ChunkDiff::New(chunk) => (&mut new_chunks, chunk),
+                _ => continue,
+            };
+            chunks.push(UnpairedChunk {
+                wad,
+                chunk: *chunk,
+                diff_index: Some((pair_index, diff_index)),
+            });
+        }
+    }
+    for (wad, chunks) in &removed_wad_chunks {
+        removed_chunks.extend(chunks.iter().map(|chunk| UnpairedChunk {
+            wad,
+            chunk: *chunk,
+            diff_index: None,
+        }));
+    }
+    for (wad, chunks) in &added_wad_chunks {
+        new_chunks.extend(chunks.iter().map(|chunk| UnpairedChunk {
+            wad,
+            chunk: *chunk,
+            diff_index: None,
+        }));
+    }
+
+    // Keep the pairing deterministic, the chunk diffs come out of hash maps
+    removed_chunks.sort_by_key(|removed| (removed.wad, removed.chunk.path_hash));
+    new_chunks.sort_by_key(|new| (new.wad, new.chunk.path_hash));
+
+    let (mut entries, moved_diffs) = collect_moved_chunks(&removed_chunks, &new_chunks, hashtable);
+    tracing::info!("Found {} chunks that moved between wads", entries.len());
+
+    for (pair_index, (wad, diffs)) in pair_diffs.iter().enumerate() {
+        entries.extend(
+            diffs
+                .iter()
+                .enumerate()
+                .filter(|(diff_index, _)| !moved_diffs.contains(&(pair_index, *diff_index)))
+                .map(|(_, diff)| DiffEntry::from_chunk_diff(diff, hashtable).with_wad(wad)),
+        );
+    }
+
+    let wads = WadsSummary {
+        compared: paired_wads.len(),
+        added: added_wads.into_iter().cloned().collect(),
+        removed: removed_wads.into_iter().cloned().collect(),
+    };
+
+    Ok(DiffReport::new(reference_dir.as_str(), target_dir.as_str(), entries).with_wads(wads))
+}
+
+/// Pairs chunks that disappeared from one wad with chunks of identical content that appeared
+/// in another wad. Returns the moved entries and the indices of the chunk diffs they replace.
+fn collect_moved_chunks(
+    removed_chunks: &[UnpairedChunk],
+    new_chunks: &[UnpairedChunk],
+    hashtable: &WadHashtable,
+) -> (Vec<DiffEntry>, HashSet<(usize, usize)>) {
+    let mut removed_by_checksum = HashMap::<u64, Vec<&UnpairedChunk>>::new();
+    for removed in removed_chunks {
+        removed_by_checksum
+            .entry(removed.chunk.checksum)
+            .or_default()
+            .push(removed);
+    }
+
+    let mut entries = Vec::new();
+    let mut moved_diffs = HashSet::new();
+    for new in new_chunks {
+        let Some(candidates) = removed_by_checksum.get_mut(&new.chunk.checksum) else {
+            continue;
+        };
+        // Prefer a chunk with the same path, otherwise take the first one from another wad
+        let Some(position) = candidates
+            .iter()
+            .position(|removed| {
+                removed.wad != new.wad && removed.chunk.path_hash == new.chunk.path_hash
+            })
+            .or_else(|| candidates.iter().position(|removed| removed.wad != new.wad))
+        else {
+            continue;
+        };
+
+        let removed = candidates.remove(position);
+        moved_diffs.extend(removed.diff_index);
+        moved_diffs.extend(new.diff_index);
+        entries.push(DiffEntry::moved(
+            removed.wad,
+            &removed.chunk,
+            new.wad,
+            &new.chunk,
+            hashtable,
+        ));
+    }
+
+    (entries, moved_diffs)
+}
+
+/// Collects all wads in the directory, keyed by their relative path with forward slashes
+fn collect_wad_paths(dir: &Utf8Path) -> eyre::Result<BTreeMap<String, Utf8PathBuf>> {
+    let mut wads = BTreeMap::new();
+    for entry in WalkDir::new(dir.as_std_path()) {
+        let entry = entry.wrap_err(format!("failed to walk directory: {}", dir))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let Ok(path) = Utf8PathBuf::from_path_buf(entry.into_path()) else {
+            continue;
+        };
+        if !is_wad_path(&path) {
+            continue;
+        }
+
+        let relative_path = path.strip_prefix(dir)?.as_str().replace('\\', "/");
+        wads.insert(relative_path, path);
+    }
+
+    Ok(wads)
+}
+
+fn read_wad_chunks(path: &Utf8Path) -> eyre::Result<Vec<WadChunk>> {
+    let file = File::open(path).wrap_err(format!("failed to open wad: {}", path))?;
+    let wad = Wad::mount(&file).wrap_err(format!("failed to mount wad: {}", path))?;
+
+    let mut chunks = wad.chunks().values().copied().collect::<Vec<_>>();
+    chunks.sort_by_key(|chunk| chunk.path_hash);
+    Ok(chunks)
+}
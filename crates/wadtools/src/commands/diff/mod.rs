@@ -0,0 +1,288 @@
+mod content;
+mod directory;
+mod report;
+
+use camino::Utf8Path;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek},
+};
+
+use colored::Colorize;
+use eyre::{eyre, Context};
+use league_toolkit::wad::{Wad, WadChunk};
+
+use crate::utils::{collect_wad_path_hashes, default_hashtable_dir, BinHashtable, WadHashtable};
+
+use content::*;
+use directory::*;
+use report::*;
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum DiffOutputFormat {
+    /// Colored human-readable output
+    Table,
+    /// JSON output for scripting
+    Json,
+    /// CSV output for spreadsheets
+    Csv,
+    /// Markdown output for patch notes and wikis
+    Markdown,
+}
+
+/// A difference between two WAD chunks
+pub enum ChunkDiff {
+    /// A new chunk in the target WAD
+    New(WadChunk),
+    /// A removed chunk in the target WAD
+    Removed(WadChunk),
+    /// A modified chunk in the target WAD
+    Modified {
+        old: WadChunk,
+        new: WadChunk,
+        /// Only present when comparing chunk contents
+        content: Option<ContentDiff>,
+    },
+    /// A renamed chunk in the target WAD
+    Renamed { old: WadChunk, new: WadChunk },
+    /// A new chunk in the target WAD with the same contents as a chunk that is still present
+    /// (or was already renamed), one source chunk can be copied many times
+    Copied { source: WadChunk, new: WadChunk },
+}
+
+pub struct DiffArgs {
+    pub reference: String,
+    pub target: String,
+    pub hashtable_path: Option<String>,
+    pub output: Option<String>,
+    pub hashtable_dir: Option<String>,
+    pub strict_hashtables: bool,
+    pub content: bool,
+    pub bin_hashtables: Vec<String>,
+    pub format: Option<DiffOutputFormat>,
+}
+
+pub fn diff(args: DiffArgs) -> eyre::Result<()> {
+    let reference_path = Utf8Path::new(&args.reference);
+    let target_path = Utf8Path::new(&args.target);
+
+    // Comparing two wads only needs the paths of their chunks
+    let mut hashtable = match reference_path.is_dir() || target_path.is_dir() {
+        true => WadHashtable::new()?,
+        false => {
+            WadHashtable::with_path_hashes(collect_wad_path_hashes([reference_path, target_path])?)?
+        }
+    };
+    hashtable.set_strict(args.strict_hashtables);
+    if let Some(dir_override) = &args.hashtable_dir {
+        hashtable.add_from_dir(Utf8Path::new(dir_override))?;
+    } else if let Some(dir) = default_hashtable_dir() {
+        hashtable.add_from_dir(dir)?;
+    }
+    if let Some(hashtable_path) = &args.hashtable_path {
+        hashtable.add_from_file(Utf8Path::new(hashtable_path))?;
+    }
+
+    let bin_hashtable = match args.content {
+        true => {
+            let mut bin_hashtable = BinHashtable::new()?;
+            bin_hashtable.set_strict(args.strict_hashtables);
+            if let Some(dir_override) = &args.hashtable_dir {
+                bin_hashtable.add_from_dir(Utf8Path::new(dir_override))?;
+            } else if let Some(dir) = default_hashtable_dir() {
+                bin_hashtable.add_from_dir(dir)?;
+            }
+            for bin_hashtable_path in &args.bin_hashtables {
+                bin_hashtable.add_from_file(Utf8Path::new(bin_hashtable_path))?;
+            }
+            Some(bin_hashtable)
+        }
+        false => None,
+    };
+
+    let report = match (reference_path.is_dir(), target_path.is_dir()) {
+        (true, true) => diff_directories(
+            reference_path,
+            target_path,
+            &hashtable,
+            bin_hashtable.as_ref(),
+        )?,
+        (false, false) => {
+            tracing::info!("Collecting diffs...");
+            let diffs = diff_wads(
+                reference_path,
+                target_path,
+                &hashtable,
+                bin_hashtable.as_ref(),
+            )?;
+
+            let entries = diffs
+                .iter()
+                .map(|diff| DiffEntry::from_chunk_diff(diff, &hashtable))
+                .collect();
+            DiffReport::new(&args.reference, &args.target, entries)
+        }
+        _ => {
+            return Err(eyre!(
+                "reference and target must either both be wad files or both be directories"
+            ))
+        }
+    };
+
+    // Writing to a file without an explicit format keeps the historical CSV behavior
+    let format = args.format.unwrap_or(match args.output {
+        Some(_) => DiffOutputFormat::Csv,
+        None => DiffOutputFormat::Table,
+    });
+
+    match &args.output {
+        Some(output_path) => {
+            tracing::info!("Writing diffs to file: {}", output_path.bright_cyan());
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(output_path)?;
+
+            colored::control::set_override(false);
+            write_report(&report, format, &mut file)?;
+            colored::control::unset_override();
+
+            tracing::info!("Wrote {} diffs to file", report.diffs.len());
+        }
+        None => write_report(&report, format, &mut io::stdout().lock())?,
+    }
+
+    Ok(())
+}
+
+/// Mounts both wads and collects their chunk diffs, comparing chunk contents if a bin
+/// hashtable is given
+fn diff_wads(
+    reference_path: &Utf8Path,
+    target_path: &Utf8Path,
+    hashtable: &WadHashtable,
+    bin_hashtable: Option<&BinHashtable>,
+) -> eyre::Result<Vec<ChunkDiff>> {
+    let reference_wad_file = File::open(reference_path)
+        .wrap_err(format!("failed to open reference wad: {}", reference_path))?;
+    let target_wad_file =
+        File::open(target_path).wrap_err(format!("failed to open target wad: {}", target_path))?;
+
+    let mut reference_wad = Wad::mount(&reference_wad_file)
+        .wrap_err(format!("failed to mount reference wad: {}", reference_path))?;
+    let mut target_wad = Wad::mount(&target_wad_file)
+        .wrap_err(format!("failed to mount target wad: {}", target_path))?;
+
+    let mut diffs = collect_diffs(&reference_wad, &target_wad);
+
+    if let Some(bin_hashtable) = bin_hashtable {
+        let (mut reference_decoder, _) = reference_wad.decode();
+        let (mut target_decoder, _) = target_wad.decode();
+        collect_content_diffs(
+            &mut diffs,
+            &mut reference_decoder,
+            &mut target_decoder,
+            hashtable,
+            bin_hashtable,
+        )?;
+    }
+
+    Ok(diffs)
+}
+
+fn write_report(
+    report: &DiffReport,
+    format: DiffOutputFormat,
+    writer: &mut impl io::Write,
+) -> eyre::Result<()> {
+    match format {
+        DiffOutputFormat::Table => write_table(report, writer),
+        DiffOutputFormat::Json => write_json(report, writer),
+        DiffOutputFormat::Csv => write_csv(report, writer),
+        DiffOutputFormat::Markdown => write_markdown(report, writer),
+    }
+}
+
+fn collect_diffs<TRefSource, TTargetSource>(
+    reference_wad: &Wad<TRefSource>,
+    target_wad: &Wad<TTargetSource>,
+) -> Vec<ChunkDiff>
+where
+    TRefSource: Read + Seek,
+    TTargetSource: Read + Seek,
+{
+    let mut diffs = Vec::<ChunkDiff>::new();
+
+    // Every reference chunk by checksum, ordered by path hash so matching is deterministic
+    let mut reference_chunks = reference_wad.chunks().values().collect::<Vec<_>>();
+    reference_chunks.sort_by_key(|chunk| chunk.path_hash);
+    let mut chunks_by_checksum = HashMap::<u64, Vec<&WadChunk>>::new();
+    for chunk in &reference_chunks {
+        chunks_by_checksum
+            .entry(chunk.checksum)
+            .or_default()
+            .push(chunk);
+    }
+
+    for reference_chunk in &reference_chunks {
+        // Chunks present in both wads are modified if their checksums differ
+        if let Some(target_chunk) = target_wad.chunks().get(&reference_chunk.path_hash) {
+            if target_chunk.checksum != reference_chunk.checksum {
+                diffs.push(ChunkDiff::Modified {
+                    old: **reference_chunk,
+                    new: *target_chunk,
+                    content: None,
+                });
+            }
+        }
+    }
+
+    let mut target_only_chunks = target_wad
+        .chunks()
+        .values()
+        .filter(|chunk| !reference_wad.chunks().contains_key(&chunk.path_hash))
+        .collect::<Vec<_>>();
+    target_only_chunks.sort_by_key(|chunk| chunk.path_hash);
+
+    // Reference-only chunks can be the source of at most one rename, otherwise they are removed
+    let mut rename_sources = HashSet::<u64>::new();
+    for target_chunk in target_only_chunks {
+        let sources = chunks_by_checksum
+            .get(&target_chunk.checksum)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        let rename_source = sources.iter().find(|chunk| {
+            !target_wad.chunks().contains_key(&chunk.path_hash)
+                && !rename_sources.contains(&chunk.path_hash)
+        });
+        if let Some(rename_source) = rename_source {
+            rename_sources.insert(rename_source.path_hash);
+            diffs.push(ChunkDiff::Renamed {
+                old: **rename_source,
+                new: *target_chunk,
+            });
+            continue;
+        }
+
+        match sources.first() {
+            Some(source) => diffs.push(ChunkDiff::Copied {
+                source: **source,
+                new: *target_chunk,
+            }),
+            None => diffs.push(ChunkDiff::New(*target_chunk)),
+        }
+    }
+
+    for reference_chunk in reference_chunks {
+        if !target_wad.chunks().contains_key(&reference_chunk.path_hash)
+            && !rename_sources.contains(&reference_chunk.path_hash)
+        {
+            diffs.push(ChunkDiff::Removed(*reference_chunk));
+        }
+    }
+
+    diffs
+}
@@ -0,0 +1,651 @@
+use std::io::Write;
+
+use colored::Colorize;
+use league_toolkit::wad::WadChunk;
+use serde::Serialize;
+
+use crate::{
+    bin_diff::{BinChange, BinChangeKind},
+    utils::{format_chunk_path_hash, WadHashtable},
+};
+
+use super::{ChunkDiff, ContentDiff};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffType {
+    New,
+    Removed,
+    Modified,
+    Renamed,
+    Copied,
+    /// A chunk that moved to another wad, only reported when diffing directories
+    Moved,
+}
+
+impl DiffType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DiffType::New => "new",
+            DiffType::Removed => "removed",
+            DiffType::Modified => "modified",
+            DiffType::Renamed => "renamed",
+            DiffType::Copied => "copied",
+            DiffType::Moved => "moved",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ChunkSizes {
+    pub compressed_size: usize,
+    pub uncompressed_size: usize,
+}
+
+impl From<&WadChunk> for ChunkSizes {
+    fn from(chunk: &WadChunk) -> Self {
+        Self {
+            compressed_size: chunk.compressed_size,
+            uncompressed_size: chunk.uncompressed_size,
+        }
+    }
+}
+
+/// A single chunk diff with resolved paths, shared by all output formats
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffEntry {
+    pub diff_type: DiffType,
+    /// Relative path of the wad containing the chunk, only set when diffing directories
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wad: Option<String>,
+    /// Relative path of the wad a moved chunk ended up in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_wad: Option<String>,
+    pub hash: String,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_path: Option<String>,
+    /// Sizes in the reference wad, `None` for new chunks
+    pub old: Option<ChunkSizes>,
+    /// Sizes in the target wad, `None` for removed chunks
+    pub new: Option<ChunkSizes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<ContentDiff>,
+}
+
+impl DiffEntry {
+    pub fn from_chunk_diff(diff: &ChunkDiff, hashtable: &WadHashtable) -> Self {
+        let entry = |diff_type, old: Option<&WadChunk>, new: Option<&WadChunk>| {
+            let path_hash = old.or(new).map(|chunk| chunk.path_hash).unwrap_or(0);
+            Self {
+                diff_type,
+                wad: None,
+                new_wad: None,
+                hash: format_chunk_path_hash(path_hash),
+                path: hashtable.resolve_path(path_hash).to_string(),
+                new_hash: None,
+                new_path: None,
+                old: old.map(ChunkSizes::from),
+                new: new.map(ChunkSizes::from),
+                content: None,
+            }
+        };
+
+        match diff {
+            ChunkDiff::New(chunk) => entry(DiffType::New, None, Some(chunk)),
+            ChunkDiff::Removed(chunk) => entry(DiffType::Removed, Some(chunk), None),
+            ChunkDiff::Modified { old, new, content } => Self {
+                content: content.clone(),
+                ..entry(DiffType::Modified, Some(old), Some(new))
+            },
+            ChunkDiff::Renamed { old, new } => Self {
+                new_hash: Some(format_chunk_path_hash(new.path_hash)),
+                new_path: Some(hashtable.resolve_path(new.path_hash).to_string()),
+                ..entry(DiffType::Renamed, Some(old), Some(new))
+            },
+            ChunkDiff::Copied { source, new } => Self {
+                new_hash: Some(format_chunk_path_hash(new.path_hash)),
+                new_path: Some(hashtable.resolve_path(new.path_hash).to_string()),
+                ..entry(DiffType::Copied, Some(source), Some(new))
+            },
+        }
+    }
+
+    /// Creates an entry for a chunk that moved from one wad to another.
+    /// `new_hash` and `new_path` are only set if the chunk was also renamed.
+    pub fn moved(
+        old_wad: &str,
+        old: &WadChunk,
+        new_wad: &str,
+        new: &WadChunk,
+        hashtable: &WadHashtable,
+    ) -> Self {
+        let renamed = old.path_hash != new.path_hash;
+        Self {
+            diff_type: DiffType::Moved,
+            wad: Some(old_wad.to_string()),
+            new_wad: Some(new_wad.to_string()),
+            hash: format_chunk_path_hash(old.path_hash),
+            path: hashtable.resolve_path(old.path_hash).to_string(),
+            new_hash: renamed.then(|| format_chunk_path_hash(new.path_hash)),
+            new_path: renamed.then(|| hashtable.resolve_path(new.path_hash).to_string()),
+            old: Some(ChunkSizes::from(old)),
+            new: Some(ChunkSizes::from(new)),
+            content: None,
+        }
+    }
+
+    pub fn with_wad(self, wad: &str) -> Self {
+        Self {
+            wad: Some(wad.to_string()),
+            ..self
+        }
+    }
+}
+
+/// Number of diffs per diff type
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct DiffSummary {
+    pub new: usize,
+    pub removed: usize,
+    pub modified: usize,
+    pub renamed: usize,
+    pub copied: usize,
+    pub moved: usize,
+    pub total: usize,
+}
+
+impl DiffSummary {
+    fn add(&mut self, diff_type: DiffType) {
+        match diff_type {
+            DiffType::New => self.new += 1,
+            DiffType::Removed => self.removed += 1,
+            DiffType::Modified => self.modified += 1,
+            DiffType::Renamed => self.renamed += 1,
+            DiffType::Copied => self.copied += 1,
+            DiffType::Moved => self.moved += 1,
+        }
+        self.total += 1;
+    }
+}
+
+/// Wads that were compared, added or removed when diffing directories
+#[derive(Debug, Default, Serialize)]
+pub struct WadsSummary {
+    pub compared: usize,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffReport {
+    pub reference: String,
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wads: Option<WadsSummary>,
+    pub summary: DiffSummary,
+    pub diffs: Vec<DiffEntry>,
+}
+
+impl DiffReport {
+    pub fn new(
+        reference: impl Into<String>,
+        target: impl Into<String>,
+        mut entries: Vec<DiffEntry>,
+    ) -> Self {
+        entries.sort_by(|a, b| {
+            a.wad
+                .cmp(&b.wad)
+                .then(a.path.cmp(&b.path))
+                .then(a.diff_type.cmp(&b.diff_type))
+        });
+
+        let mut summary = DiffSummary::default();
+        for entry in &entries {
+            summary.add(entry.diff_type);
+        }
+
+        Self {
+            reference: reference.into(),
+            target: target.into(),
+            wads: None,
+            summary,
+            diffs: entries,
+        }
+    }
+
+    pub fn with_wads(self, wads: WadsSummary) -> Self {
+        Self {
+            wads: Some(wads),
+            ..self
+        }
+    }
+}
+
+pub fn write_table(report: &DiffReport, writer: &mut impl Write) -> eyre::Result<()> {
+    if let Some(wads) = &report.wads {
+        for wad in &wads.added {
+            writeln!(
+                writer,
+                "+ {} {}",
+                wad.bright_green().bold(),
+                "(wad)".bright_black()
+            )?;
+        }
+        for wad in &wads.removed {
+            writeln!(
+                writer,
+                "- {} {}",
+                wad.bright_red().bold(),
+                "(wad)".bright_black()
+            )?;
+        }
+    }
+
+    let mut current_wad = None;
+    for entry in &report.diffs {
+        if entry.wad.is_some() && entry.wad != current_wad {
+            current_wad = entry.wad.clone();
+            writeln!(writer, "{}", entry.wad.as_deref().unwrap_or("").bold())?;
+        }
+
+        match entry.diff_type {
+            DiffType::New => writeln!(writer, "+ {}", entry.path.bright_green())?,
+            DiffType::Removed => writeln!(writer, "- {}", entry.path.bright_red())?,
+            DiffType::Renamed => writeln!(
+                writer,
+                "! {} -> {}",
+                entry.path.bright_blue(),
+                entry.new_path.as_deref().unwrap_or("").bright_cyan()
+            )?,
+            DiffType::Copied => writeln!(
+                writer,
+                "+ {} {}",
+                entry.new_path.as_deref().unwrap_or("").green(),
+                format!("(copy of {})", entry.path).bright_black()
+            )?,
+            DiffType::Moved => writeln!(
+                writer,
+                "> {} -> {}",
+                entry.path.bright_magenta(),
+                match &entry.new_path {
+                    Some(new_path) =>
+                        format!("{}:{}", entry.new_wad.as_deref().unwrap_or(""), new_path),
+                    None => entry.new_wad.clone().unwrap_or_default(),
+                }
+                .bright_cyan()
+            )?,
+            DiffType::Modified => {
+                let Some(content) = &entry.content else {
+                    writeln!(writer, "! {}", entry.path.bright_yellow())?;
+                    continue;
+                };
+
+                match content.first_difference {
+                    Some(offset) => writeln!(
+                        writer,
+                        "! {} ({:+} B, {} B changed, first at 0x{:x})",
+                        entry.path.bright_yellow(),
+                        content.size_delta,
+                        content.changed_bytes,
+                        offset
+                    )?,
+                    None => writeln!(
+                        writer,
+                        "! {} {}",
+                        entry.path.bright_yellow(),
+                        "(content identical)".bright_black()
+                    )?,
+                }
+                if let Some(unified_diff) = &content.unified_diff {
+                    write_unified_diff(unified_diff, writer)?;
+                }
+                if let Some(bin_changes) = &content.bin_changes {
+                    write_bin_changes(bin_changes, writer)?;
+                }
+            }
+        }
+    }
+
+    let summary = &report.summary;
+    write!(
+        writer,
+        "{} new, {} removed, {} modified, {} renamed, {} copied",
+        summary.new.to_string().bright_green(),
+        summary.removed.to_string().bright_red(),
+        summary.modified.to_string().bright_yellow(),
+        summary.renamed.to_string().bright_blue(),
+        summary.copied.to_string().green()
+    )?;
+    match &report.wads {
+        Some(wads) => writeln!(
+            writer,
+            ", {} moved ({} wads compared, {} added, {} removed)",
+            summary.moved.to_string().bright_magenta(),
+            wads.compared,
+            wads.added.len().to_string().bright_green(),
+            wads.removed.len().to_string().bright_red()
+        )?,
+        None => writeln!(writer)?,
+    }
+
+    Ok(())
+}
+
+fn write_unified_diff(unified_diff: &str, writer: &mut impl Write) -> eyre::Result<()> {
+    for line in unified_diff.lines() {
+        if line.starts_with("+++") || line.starts_with("---") {
+            writeln!(writer, "    {}", line.bold())?;
+        } else if line.starts_with('+') {
+            writeln!(writer, "    {}", line.green())?;
+        } else if line.starts_with('-') {
+            writeln!(writer, "    {}", line.red())?;
+        } else if line.starts_with("@@") {
+            writeln!(writer, "    {}", line.cyan())?;
+        } else {
+            writeln!(writer, "    {}", line)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_bin_changes(changes: &[BinChange], writer: &mut impl Write) -> eyre::Result<()> {
+    for change in changes {
+        let target = bin_change_target(change);
+        match change.kind {
+            BinChangeKind::Added => match &change.new_value {
+                Some(value) => writeln!(writer, "    + {} = {}", target.green(), value)?,
+                None => writeln!(writer, "    + {}", target.green())?,
+            },
+            BinChangeKind::Removed => match &change.old_value {
+                Some(value) => writeln!(writer, "    - {} = {}", target.red(), value)?,
+                None => writeln!(writer, "    - {}", target.red())?,
+            },
+            BinChangeKind::Changed => writeln!(
+                writer,
+                "    ~ {}: {} -> {}",
+                target.yellow(),
+                change.old_value.as_deref().unwrap_or("").bright_red(),
+                change.new_value.as_deref().unwrap_or("").bright_green()
+            )?,
+        }
+    }
+
+    Ok(())
+}
+
+fn bin_change_target(change: &BinChange) -> String {
+    match &change.field {
+        Some(field) => format!("{}.{}", change.object, field),
+        None if change.class.is_empty() => change.object.clone(),
+        None => format!("{} ({})", change.object, change.class),
+    }
+}
+
+pub fn write_json(report: &DiffReport, writer: &mut impl Write) -> eyre::Result<()> {
+    serde_json::to_writer_pretty(&mut *writer, report)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// A record for a chunk diff in a CSV file
+#[derive(Debug, Serialize)]
+struct ChunkDiffCsvRecord<'a> {
+    diff_type: &'a str,
+    wad: &'a str,
+    hash: &'a str,
+    path: &'a str,
+    new_wad: &'a str,
+    new_path: &'a str,
+    old_compressed_size: Option<usize>,
+    old_uncompressed_size: Option<usize>,
+    new_compressed_size: Option<usize>,
+    new_uncompressed_size: Option<usize>,
+    first_difference_offset: Option<usize>,
+    changed_bytes: Option<usize>,
+    bin_object: &'a str,
+    bin_class: &'a str,
+    bin_field: &'a str,
+    bin_old_value: &'a str,
+    bin_new_value: &'a str,
+}
+
+impl<'a> ChunkDiffCsvRecord<'a> {
+    fn from_entry(entry: &'a DiffEntry) -> Self {
+        Self {
+            diff_type: entry.diff_type.as_str(),
+            wad: entry.wad.as_deref().unwrap_or(""),
+            hash: &entry.hash,
+            path: &entry.path,
+            new_wad: entry.new_wad.as_deref().unwrap_or(""),
+            new_path: entry.new_path.as_deref().unwrap_or(""),
+            old_compressed_size: entry.old.map(|s| s.compressed_size),
+            old_uncompressed_size: entry.old.map(|s| s.uncompressed_size),
+            new_compressed_size: entry.new.map(|s| s.compressed_size),
+            new_uncompressed_size: entry.new.map(|s| s.uncompressed_size),
+            first_difference_offset: entry.content.as_ref().and_then(|c| c.first_difference),
+            changed_bytes: entry.content.as_ref().map(|c| c.changed_bytes),
+            bin_object: "",
+            bin_class: "",
+            bin_field: "",
+            bin_old_value: "",
+            bin_new_value: "",
+        }
+    }
+
+    fn from_bin_change(entry: &'a DiffEntry, change: &'a BinChange) -> Self {
+        let diff_type = match change.kind {
+            BinChangeKind::Added => "bin_added",
+            BinChangeKind::Removed => "bin_removed",
+            BinChangeKind::Changed => "bin_changed",
+        };
+
+        Self {
+            diff_type,
+            first_difference_offset: None,
+            changed_bytes: None,
+            bin_object: &change.object,
+            bin_class: &change.class,
+            bin_field: change.field.as_deref().unwrap_or(""),
+            bin_old_value: change.old_value.as_deref().unwrap_or(""),
+            bin_new_value: change.new_value.as_deref().unwrap_or(""),
+            ..Self::from_entry(entry)
+        }
+    }
+
+    fn from_wad(diff_type: &'a str, wad: &'a str) -> Self {
+        Self {
+            diff_type,
+            wad,
+            hash: "",
+            path: "",
+            new_wad: "",
+            new_path: "",
+            old_compressed_size: None,
+            old_uncompressed_size: None,
+            new_compressed_size: None,
+            new_uncompressed_size: None,
+            first_difference_offset: None,
+            changed_bytes: None,
+            bin_object: "",
+            bin_class: "",
+            bin_field: "",
+            bin_old_value: "",
+            bin_new_value: "",
+        }
+    }
+}
+
+pub fn write_csv(report: &DiffReport, writer: &mut impl Write) -> eyre::Result<()> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+
+    if let Some(wads) = &report.wads {
+        for wad in &wads.added {
+            csv_writer.serialize(ChunkDiffCsvRecord::from_wad("wad_added", wad))?;
+        }
+        for wad in &wads.removed {
+            csv_writer.serialize(ChunkDiffCsvRecord::from_wad("wad_removed", wad))?;
+        }
+    }
+
+    for entry in &report.diffs {
+        csv_writer.serialize(ChunkDiffCsvRecord::from_entry(entry))?;
+
+        let bin_changes = entry.content.as_ref().and_then(|c| c.bin_changes.as_ref());
+        for change in bin_changes.into_iter().flatten() {
+            csv_writer.serialize(ChunkDiffCsvRecord::from_bin_change(entry, change))?;
+        }
+    }
+
+    csv_writer.flush()?;
+    Ok(())
+}
+
+pub fn write_markdown(report: &DiffReport, writer: &mut impl Write) -> eyre::Result<()> {
+    let summary = &report.summary;
+    writeln!(writer, "# WAD diff")?;
+    writeln!(writer)?;
+    writeln!(writer, "- Reference: `{}`", report.reference)?;
+    writeln!(writer, "- Target: `{}`", report.target)?;
+    writeln!(writer)?;
+    writeln!(writer, "| Type | Count |")?;
+    writeln!(writer, "| --- | ---: |")?;
+    writeln!(writer, "| New | {} |", summary.new)?;
+    writeln!(writer, "| Removed | {} |", summary.removed)?;
+    writeln!(writer, "| Modified | {} |", summary.modified)?;
+    writeln!(writer, "| Renamed | {} |", summary.renamed)?;
+    writeln!(writer, "| Copied | {} |", summary.copied)?;
+    if report.wads.is_some() {
+        writeln!(writer, "| Moved | {} |", summary.moved)?;
+    }
+    writeln!(writer, "| **Total** | **{}** |", summary.total)?;
+
+    if let Some(wads) = &report.wads {
+        writeln!(writer)?;
+        writeln!(writer, "## WADs")?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "{} compared, {} added, {} removed",
+            wads.compared,
+            wads.added.len(),
+            wads.removed.len()
+        )?;
+        if !wads.added.is_empty() || !wads.removed.is_empty() {
+            writeln!(writer)?;
+        }
+        for wad in &wads.added {
+            writeln!(writer, "- Added `{}`", wad)?;
+        }
+        for wad in &wads.removed {
+            writeln!(writer, "- Removed `{}`", wad)?;
+        }
+    }
+
+    if report.diffs.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "## Changes")?;
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "| Type | Path | Old compressed | Old uncompressed | New compressed | New uncompressed |"
+    )?;
+    writeln!(writer, "| --- | --- | ---: | ---: | ---: | ---: |")?;
+    for entry in &report.diffs {
+        let qualify = |wad: Option<&String>, path: &str| match wad {
+            Some(wad) => format!("`{}:{}`", wad, path),
+            None => format!("`{}`", path),
+        };
+        let old_path = qualify(entry.wad.as_ref(), &entry.path);
+        let path = match (&entry.new_path, &entry.new_wad) {
+            (None, None) => old_path,
+            (new_path, new_wad) => format!(
+                "{} → {}",
+                old_path,
+                qualify(
+                    new_wad.as_ref().or(entry.wad.as_ref()),
+                    new_path.as_deref().unwrap_or(&entry.path)
+                )
+            ),
+        };
+        let sizes = |sizes: Option<ChunkSizes>| {
+            sizes.map_or(" | ".to_string(), |s| {
+                format!("{} | {}", s.compressed_size, s.uncompressed_size)
+            })
+        };
+
+        writeln!(
+            writer,
+            "| {} | {} | {} | {} |",
+            entry.diff_type.as_str(),
+            path.replace('|', "\\|"),
+            sizes(entry.old),
+            sizes(entry.new)
+        )?;
+    }
+
+    let content_entries = report
+        .diffs
+        .iter()
+        .filter_map(|entry| Some((entry, entry.content.as_ref()?)))
+        .filter(|(_, content)| content.unified_diff.is_some() || content.bin_changes.is_some())
+        .collect::<Vec<_>>();
+    if content_entries.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "## Content changes")?;
+    for (entry, content) in content_entries {
+        writeln!(writer)?;
+        match &entry.wad {
+            Some(wad) => writeln!(writer, "### `{}:{}`", wad, entry.path)?,
+            None => writeln!(writer, "### `{}`", entry.path)?,
+        }
+        writeln!(writer)?;
+
+        if let Some(unified_diff) = &content.unified_diff {
+            writeln!(writer, "```diff")?;
+            write!(writer, "{}", unified_diff)?;
+            writeln!(writer, "```")?;
+        }
+        for change in content.bin_changes.iter().flatten() {
+            let target = bin_change_target(change);
+            match change.kind {
+                BinChangeKind::Added => writeln!(
+                    writer,
+                    "- Added `{}`{}",
+                    target,
+                    change
+                        .new_value
+                        .as_ref()
+                        .map_or(String::new(), |v| format!(" = `{}`", v))
+                )?,
+                BinChangeKind::Removed => writeln!(
+                    writer,
+                    "- Removed `{}`{}",
+                    target,
+                    change
+                        .old_value
+                        .as_ref()
+                        .map_or(String::new(), |v| format!(" = `{}`", v))
+                )?,
+                BinChangeKind::Changed => writeln!(
+                    writer,
+                    "- Changed `{}`: `{}` → `{}`",
+                    target,
+                    change.old_value.as_deref().unwrap_or(""),
+                    change.new_value.as_deref().unwrap_or("")
+                )?,
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,287 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use eyre::{eyre, Context};
+use league_toolkit::{file::LeagueFileKind, wad::Wad};
+use rayon::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::File,
+};
+use tracing_indicatif::span_ext::IndicatifSpanExt;
+use tracing_indicatif::style::ProgressStyle;
+
+use crate::utils::{
+    append_hashtable_entries, collect_input_wads, collect_wad_path_hashes, default_hashtable_dir,
+    format_chunk_path_hash, hash_chunk_path, normalize_chunk_path, WadHashtable,
+};
+
+/// Shortest and longest byte runs considered as path candidates
+const MIN_PATH_CANDIDATE_LEN: usize = 5;
+const MAX_PATH_CANDIDATE_LEN: usize = 512;
+
+pub struct DiscoverHashesArgs {
+    pub input: Vec<String>,
+    /// Hashtable file the discovered entries are appended to, printed to stdout when not set
+    pub output: Option<String>,
+    pub hashtable: Option<String>,
+    pub hashtable_dir: Option<String>,
+    pub strict_hashtables: bool,
+}
+
+pub fn discover_hashes(args: DiscoverHashesArgs) -> eyre::Result<()> {
+    let wads = collect_input_wads(&args.input)?;
+    if wads.is_empty() {
+        return Err(eyre!(
+            "No wad files found in input: {}",
+            args.input.join(", ")
+        ));
+    }
+
+    let path_hashes = collect_wad_path_hashes(wads.iter().map(|wad| wad.path.as_path()))?;
+    let mut hashtable = WadHashtable::with_path_hashes(path_hashes.iter().copied())?;
+    hashtable.set_strict(args.strict_hashtables);
+    if let Some(dir_override) = &args.hashtable_dir {
+        hashtable.add_from_dir(Utf8Path::new(dir_override))?;
+    } else if let Some(dir) = default_hashtable_dir() {
+        hashtable.add_from_dir(dir)?;
+    }
+    if let Some(hashtable_path) = &args.hashtable {
+        tracing::info!("loading hashtable from {}", hashtable_path);
+        hashtable.add_from_file(Utf8Path::new(hashtable_path))?;
+    }
+    // Don't append entries discovered by a previous run again
+    if let Some(output) = &args.output {
+        if Utf8Path::new(output).is_file() {
+            hashtable.add_from_file(Utf8Path::new(output))?;
+        }
+    }
+
+    let unknown_hashes = path_hashes
+        .into_iter()
+        .filter(|path_hash| !hashtable.contains(*path_hash))
+        .collect::<HashSet<_>>();
+    if unknown_hashes.is_empty() {
+        tracing::info!("all chunk paths are already known :)");
+        return Ok(());
+    }
+    tracing::info!(
+        "scanning {} wads for {} unknown chunk paths...",
+        wads.len(),
+        unknown_hashes.len()
+    );
+
+    let span = tracing::info_span!("discover_hashes", total = wads.len());
+    span.pb_set_style(
+        &ProgressStyle::with_template("{wide_bar:40.cyan/blue} {pos}/{len} \n {spinner} {msg}")
+            .unwrap(),
+    );
+    span.pb_set_length(wads.len() as u64);
+    span.pb_set_message("Scanning wads");
+    span.pb_set_finish_message("Scanning complete");
+
+    let wad_discoveries = span.in_scope(|| {
+        wads.par_iter()
+            .map(|wad| {
+                let discovered = discover_wad_paths(&wad.path, &unknown_hashes)?;
+                span.pb_inc(1);
+                Ok(discovered)
+            })
+            .collect::<eyre::Result<Vec<_>>>()
+    })?;
+
+    let mut discovered = BTreeMap::<u64, String>::new();
+    for wad_discovered in wad_discoveries {
+        for (path_hash, path) in wad_discovered {
+            discovered.entry(path_hash).or_insert(path);
+        }
+    }
+
+    match &args.output {
+        Some(output) => {
+            append_hashtable_entries(Utf8Path::new(output), &discovered)?;
+            for (path_hash, path) in &discovered {
+                tracing::info!("{} {}", format_chunk_path_hash(*path_hash), path);
+            }
+        }
+        None => {
+            for (path_hash, path) in &discovered {
+                println!("{} {}", format_chunk_path_hash(*path_hash), path);
+            }
+        }
+    }
+
+    tracing::info!(
+        "discovered {} of {} unknown chunk paths",
+        discovered.len(),
+        unknown_hashes.len()
+    );
+
+    Ok(())
+}
+
+/// Scans the decompressed data of every chunk in the wad for strings that hash to one of the
+/// unknown path hashes
+fn discover_wad_paths(
+    wad_path: &Utf8PathBuf,
+    unknown_hashes: &HashSet<u64>,
+) -> eyre::Result<HashMap<u64, String>> {
+    let source =
+        File::open(wad_path.as_std_path()).wrap_err(format!("failed to open wad: {}", wad_path))?;
+    let mut wad = Wad::mount(&source).wrap_err(format!("failed to mount wad: {}", wad_path))?;
+    let (mut decoder, chunks) = wad.decode();
+
+    let mut chunks = chunks.values().collect::<Vec<_>>();
+    chunks.sort_by_key(|chunk| chunk.path_hash);
+
+    let mut discovered = HashMap::new();
+    for chunk in chunks {
+        let chunk_data = decoder.load_chunk_decompressed(chunk).wrap_err(format!(
+            "failed to decompress chunk (chunk_hash: {}, wad: {})",
+            format_chunk_path_hash(chunk.path_hash),
+            wad_path
+        ))?;
+        if !may_contain_paths(LeagueFileKind::identify_from_bytes(&chunk_data)) {
+            continue;
+        }
+
+        for candidate in find_path_candidates(&chunk_data) {
+            for path in path_variants(candidate) {
+                let path_hash = hash_chunk_path(&path);
+                if unknown_hashes.contains(&path_hash) {
+                    discovered.entry(path_hash).or_insert(path);
+                }
+            }
+        }
+    }
+
+    Ok(discovered)
+}
+
+/// Images and audio are large and never reference other chunks, skip scanning them
+fn may_contain_paths(kind: LeagueFileKind) -> bool {
+    !matches!(
+        kind,
+        LeagueFileKind::Jpeg
+            | LeagueFileKind::Png
+            | LeagueFileKind::Tga
+            | LeagueFileKind::Texture
+            | LeagueFileKind::TextureDds
+            | LeagueFileKind::WwiseBank
+            | LeagueFileKind::WwisePackage
+    )
+}
+
+fn is_path_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'-' | b'.' | b'/' | b'\\')
+}
+
+/// Finds runs of path characters that contain a directory separator and end in an extension
+fn find_path_candidates(data: &[u8]) -> impl Iterator<Item = &str> {
+    data.split(|byte| !is_path_byte(*byte))
+        .filter(|run| {
+            (MIN_PATH_CANDIDATE_LEN..=MAX_PATH_CANDIDATE_LEN).contains(&run.len())
+                && run.iter().any(|byte| matches!(byte, b'/' | b'\\'))
+        })
+        // Only path bytes, always ASCII
+        .filter_map(|run| std::str::from_utf8(run).ok())
+        .filter(|run| {
+            let file_name = run.rsplit(['/', '\\']).next().unwrap_or_default();
+            file_name.rsplit_once('.').is_some_and(|(stem, extension)| {
+                !stem.is_empty()
+                    && (1..=8).contains(&extension.len())
+                    && extension.bytes().all(|byte| byte.is_ascii_alphanumeric())
+            })
+        })
+}
+
+/// Normalized paths a candidate string could stand for
+fn path_variants(candidate: &str) -> Vec<String> {
+    // JSON escapes forward slashes
+    let normalized = normalize_chunk_path(&candidate.replace("\\/", "/"));
+    let path = normalized.trim_start_matches('/');
+
+    let mut variants = vec![path.to_string()];
+    // Property bin strings are prefixed with their length, which can look like a path character
+    if let Some((_, rest)) = path.split_at_checked(1) {
+        if !rest.starts_with('/') {
+            variants.push(rest.to_string());
+        }
+    }
+    // Bins still reference textures as .dds, while the wads ship them as .tex
+    for index in 0..variants.len() {
+        if let Some(stem) = variants[index].strip_suffix(".dds") {
+            let texture = format!("{}.tex", stem);
+            variants.push(texture);
+        }
+    }
+
+    variants
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_path_runs_in_binary_data() {
+        let mut data = Vec::new();
+        // A property bin string, prefixed with its u16 length
+        data.extend_from_slice(&[0x1e, 0x00]);
+        data.extend_from_slice(b"ASSETS/Characters/Foo/skin.dds");
+        data.extend_from_slice(b"\x00\xffDATA\\Menu\\main.bin\x00");
+        // A length prefix that is a path character sticks to the path
+        data.extend_from_slice(&[0x41, 0x00]);
+        data.extend_from_slice(b"Aassets/x.bin\x00");
+        // JSON escapes forward slashes
+        data.extend_from_slice(br#"{"icon":"assets\/ui\/icon.png"}"#);
+        // No directory, no extension, an overlong extension and a trailing dot
+        data.extend_from_slice(b"\x00notapath.bin\x00a/b/c\x00a/b.toolongext\x00a/b.\x00");
+        let too_long = format!("a/{}.bin", "x".repeat(MAX_PATH_CANDIDATE_LEN));
+        data.extend_from_slice(too_long.as_bytes());
+
+        assert_eq!(
+            find_path_candidates(&data).collect::<Vec<_>>(),
+            [
+                "ASSETS/Characters/Foo/skin.dds",
+                "DATA\\Menu\\main.bin",
+                "Aassets/x.bin",
+                "assets\\/ui\\/icon.png",
+            ]
+        );
+    }
+
+    #[test]
+    fn path_variants_strip_length_prefixes() {
+        assert_eq!(
+            path_variants("Aassets/x.bin"),
+            ["aassets/x.bin", "assets/x.bin"]
+        );
+        // A prefix that would leave an absolute path is not stripped
+        assert_eq!(path_variants("a/b.bin"), ["a/b.bin"]);
+        assert_eq!(path_variants("/data/x.bin"), ["data/x.bin", "ata/x.bin"]);
+    }
+
+    #[test]
+    fn path_variants_normalize_candidates() {
+        assert_eq!(
+            path_variants("DATA\\Menu\\main.bin"),
+            ["data/menu/main.bin", "ata/menu/main.bin"]
+        );
+        assert_eq!(
+            path_variants("assets\\/ui\\/icon.png"),
+            ["assets/ui/icon.png", "ssets/ui/icon.png"]
+        );
+    }
+
+    #[test]
+    fn path_variants_try_dds_textures_as_tex() {
+        assert_eq!(
+            path_variants("ASSETS/Foo/bar.dds"),
+            [
+                "assets/foo/bar.dds",
+                "ssets/foo/bar.dds",
+                "assets/foo/bar.tex",
+                "ssets/foo/bar.tex",
+            ]
+        );
+    }
+}
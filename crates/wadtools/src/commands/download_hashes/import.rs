@@ -0,0 +1,240 @@
+use std::{fs::File, path::Path};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use eyre::{eyre, Context, Result};
+use flate2::read::GzDecoder;
+use walkdir::WalkDir;
+use zip::ZipArchive;
+
+use super::{install_hashtable, DownloadMetadata, HashtableWriter};
+use crate::utils::config::HashSource;
+
+/// A file found in an import source
+struct ImportEntry {
+    /// Path inside the source, with forward slashes
+    path: String,
+    size: u64,
+    /// Position of the entry in its archive
+    index: usize,
+}
+
+impl ImportEntry {
+    fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or_default()
+    }
+
+    fn parent(&self) -> &str {
+        self.path.rsplit_once('/').map_or("", |(parent, _)| parent)
+    }
+}
+
+/// Where hashtables are imported from. A git checkout of the data repository is a directory.
+enum ImportSource {
+    Directory(Utf8PathBuf),
+    Zip(ZipArchive<File>),
+    TarGz(Utf8PathBuf),
+}
+
+impl ImportSource {
+    fn open(path: &Utf8Path) -> Result<Self> {
+        if path.is_dir() {
+            return Ok(ImportSource::Directory(path.to_path_buf()));
+        }
+        if !path.is_file() {
+            return Err(eyre!("Import source not found: {}", path));
+        }
+
+        let name = path.file_name().unwrap_or_default().to_lowercase();
+        if name.ends_with(".zip") {
+            let file = File::open(path.as_std_path())?;
+            let archive =
+                ZipArchive::new(file).wrap_err(format!("Failed to open zip archive: {}", path))?;
+            Ok(ImportSource::Zip(archive))
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Ok(ImportSource::TarGz(path.to_path_buf()))
+        } else {
+            Err(eyre!(
+                "Unsupported import source: {} (expected a directory, .zip or .tar.gz)",
+                path
+            ))
+        }
+    }
+
+    fn entries(&mut self) -> Result<Vec<ImportEntry>> {
+        let mut entries = Vec::new();
+        match self {
+            ImportSource::Directory(dir) => {
+                for entry in WalkDir::new(dir.as_std_path())
+                    .sort_by_file_name()
+                    .into_iter()
+                    .filter_entry(|entry| entry.file_name() != ".git")
+                    .filter_map(|x| x.ok())
+                {
+                    if !entry.file_type().is_file() {
+                        continue;
+                    }
+                    let Some(path) = entry
+                        .path()
+                        .strip_prefix(dir.as_std_path())
+                        .ok()
+                        .and_then(Path::to_str)
+                    else {
+                        tracing::debug!("skipping non UTF-8 path: {:?}", entry.path());
+                        continue;
+                    };
+
+                    entries.push(ImportEntry {
+                        path: path.replace('\\', "/"),
+                        size: entry.metadata()?.len(),
+                        index: entries.len(),
+                    });
+                }
+            }
+            ImportSource::Zip(archive) => {
+                for index in 0..archive.len() {
+                    let file = archive.by_index_raw(index)?;
+                    if file.is_file() {
+                        entries.push(ImportEntry {
+                            path: file.name().replace('\\', "/"),
+                            size: file.size(),
+                            index,
+                        });
+                    }
+                }
+            }
+            ImportSource::TarGz(path) => {
+                let mut archive = open_tar_gz(path)?;
+                for (index, entry) in archive.entries()?.enumerate() {
+                    let entry = entry.wrap_err(format!("Failed to read archive: {}", path))?;
+                    if entry.header().entry_type().is_file() {
+                        entries.push(ImportEntry {
+                            path: entry.path()?.to_string_lossy().replace('\\', "/"),
+                            size: entry.size(),
+                            index,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn copy_entry(&mut self, entry: &ImportEntry, writer: &mut HashtableWriter) -> Result<()> {
+        match self {
+            ImportSource::Directory(dir) => {
+                let mut file = File::open(dir.join(&entry.path).as_std_path())?;
+                writer.copy_from(&mut file)?;
+            }
+            ImportSource::Zip(archive) => {
+                writer.copy_from(&mut archive.by_index(entry.index)?)?;
+            }
+            ImportSource::TarGz(path) => {
+                // Tarballs can't seek, read up to the entry again
+                let mut archive = open_tar_gz(path)?;
+                let mut tar_entry = archive
+                    .entries()?
+                    .nth(entry.index)
+                    .ok_or_else(|| eyre!("{} is missing from {}", entry.path, path))??;
+                writer.copy_from(&mut tar_entry)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn open_tar_gz(path: &Utf8Path) -> Result<tar::Archive<GzDecoder<File>>> {
+    let file = File::open(path.as_std_path()).wrap_err(format!("Failed to open {}", path))?;
+    Ok(tar::Archive::new(GzDecoder::new(file)))
+}
+
+/// Installs the hashtables of the given sources from a local directory, archive or checkout.
+/// Hashtables missing from the import source are skipped, unless they were explicitly selected.
+pub fn import_hashes(
+    from: &Utf8Path,
+    sources: &[&HashSource],
+    explicit: bool,
+    target_dir: &Utf8Path,
+) -> Result<()> {
+    let mut import_source = ImportSource::open(from)?;
+    let entries = import_source.entries()?;
+
+    let mut imported_count = 0;
+    for source in sources {
+        let filename = source.filename()?;
+        let parts = find_hashtable_parts(&entries, filename)?;
+        if parts.is_empty() {
+            if explicit {
+                return Err(eyre!("No {} found in {}", filename, from));
+            }
+            tracing::warn!("No {} found in {}, skipping", filename, from);
+            continue;
+        }
+
+        let total = parts.iter().map(|part| part.size).sum();
+        let installed = install_hashtable(filename, target_dir, Some(total), |writer| {
+            for part in &parts {
+                import_source
+                    .copy_entry(part, writer)
+                    .wrap_err(format!("Failed to import {} from {}", part.path, from))?;
+            }
+            Ok(())
+        })?;
+        // The validators belong to the replaced download
+        DownloadMetadata::remove(&installed.path)?;
+        imported_count += 1;
+    }
+
+    if imported_count == 0 {
+        return Err(eyre!("No hashtables found in {}", from));
+    }
+    Ok(())
+}
+
+/// Finds the file of a hashtable, preferring the least nested one when there are several. Falls
+/// back to numbered parts (`hashes.game.txt.0`, `hashes.game.txt.1`, ...), which is how the
+/// data repository stores hashtables too large for git hosting.
+fn find_hashtable_parts<'a>(
+    entries: &'a [ImportEntry],
+    filename: &str,
+) -> Result<Vec<&'a ImportEntry>> {
+    if let Some(entry) = entries
+        .iter()
+        .filter(|entry| entry.file_name() == filename)
+        .min_by_key(|entry| (entry.path.matches('/').count(), &entry.path))
+    {
+        return Ok(vec![entry]);
+    }
+
+    let mut parts = entries
+        .iter()
+        .filter_map(|entry| {
+            let number = entry
+                .file_name()
+                .strip_prefix(filename)?
+                .strip_prefix('.')?
+                .parse::<usize>()
+                .ok()?;
+            Some((entry.parent(), number, entry))
+        })
+        .collect::<Vec<_>>();
+    parts.sort_by_key(|(parent, number, _)| (parent.matches('/').count(), *parent, *number));
+    let Some((first_parent, _, _)) = parts.first().copied() else {
+        return Ok(Vec::new());
+    };
+    parts.retain(|(parent, _, _)| *parent == first_parent);
+
+    for (expected, (_, number, entry)) in parts.iter().enumerate() {
+        if *number != expected {
+            return Err(eyre!(
+                "Part {} of {} is missing (found {})",
+                expected,
+                filename,
+                entry.path
+            ));
+        }
+    }
+
+    Ok(parts.into_iter().map(|(_, _, entry)| entry).collect())
+}
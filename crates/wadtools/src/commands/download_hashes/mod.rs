@@ -0,0 +1,495 @@
+mod import;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use tracing::Span;
+use tracing_indicatif::span_ext::IndicatifSpanExt;
+
+use crate::utils::config::{default_hash_sources, HashSource};
+use crate::utils::{
+    default_hashtable_dir, for_each_hashtable_entry, PartialFile, DOWNLOAD_METADATA_SUFFIX,
+    WAD_HASH_DIGITS,
+};
+
+use import::*;
+
+/// Format a path as a clickable hyperlink using OSC 8 escape sequences.
+/// Supported by modern terminals like Windows Terminal, iTerm2, VS Code terminal, etc.
+fn hyperlink_path(path: &Utf8PathBuf) -> String {
+    let url = format!("file://{}", path.as_str().replace('\\', "/"));
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, path)
+}
+
+const DOWNLOAD_BUFFER_SIZE: usize = 64 * 1024;
+
+pub struct DownloadHashesArgs {
+    pub hashtable_dir: Option<String>,
+    /// Configured `[[hash_sources]]`, the CommunityDragon hashtables when empty
+    pub hash_sources: Vec<HashSource>,
+    /// Names of the sources to download, all of them when empty
+    pub sources: Vec<String>,
+    pub list_sources: bool,
+    /// Directory, .zip/.tar.gz bundle or data repository checkout to install the hashtables from
+    /// instead of downloading them
+    pub from: Option<String>,
+}
+
+pub fn download_hashes(args: DownloadHashesArgs) -> Result<()> {
+    let hash_sources = match args.hash_sources.is_empty() {
+        true => default_hash_sources(),
+        false => args.hash_sources,
+    };
+    let mut names = HashSet::new();
+    for source in &hash_sources {
+        if !names.insert(source.name.as_str()) {
+            return Err(eyre!("Hash source `{}` is configured twice", source.name));
+        }
+        source.filename()?;
+    }
+
+    if args.list_sources {
+        for source in &hash_sources {
+            println!("{} {} -> {}", source.name, source.url, source.filename()?);
+        }
+        return Ok(());
+    }
+
+    let selected_sources = match args.sources.is_empty() {
+        true => hash_sources.iter().collect::<Vec<_>>(),
+        false => args
+            .sources
+            .iter()
+            .map(|name| {
+                hash_sources
+                    .iter()
+                    .find(|source| source.name == *name)
+                    .ok_or_else(|| {
+                        eyre!(
+                            "Unknown hash source `{}` (available: {})",
+                            name,
+                            hash_sources
+                                .iter()
+                                .map(|source| source.name.as_str())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?,
+    };
+
+    let target_dir = match args.hashtable_dir {
+        Some(dir) => Utf8Path::new(&dir).to_path_buf(),
+        None => default_hashtable_dir()
+            .ok_or_else(|| eyre!("Could not determine default hashtable directory"))?,
+    };
+
+    fs::create_dir_all(target_dir.as_std_path())?;
+
+    if let Some(from) = &args.from {
+        tracing::info!("Importing hashtables from {}", from);
+        import_hashes(
+            Utf8Path::new(from),
+            &selected_sources,
+            !args.sources.is_empty(),
+            &target_dir,
+        )?;
+        tracing::info!(
+            "Successfully imported hashtables to {}",
+            hyperlink_path(&target_dir)
+        );
+        return Ok(());
+    }
+
+    tracing::info!("Downloading hashtables to {}", hyperlink_path(&target_dir));
+
+    let mut new_hash_count = 0;
+    for source in selected_sources {
+        if let Some(installed) =
+            download_file_with_progress(&source.url, source.filename()?, &target_dir)?
+        {
+            new_hash_count += installed.new_hash_count;
+        }
+    }
+
+    tracing::info!(
+        "Successfully downloaded all hashtables to {} ({} new hashes)",
+        hyperlink_path(&target_dir),
+        new_hash_count
+    );
+    Ok(())
+}
+
+/// Validators of a downloaded hashtable, stored next to it to make the next download conditional
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DownloadMetadata {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl DownloadMetadata {
+    fn path(target_path: &Utf8Path) -> Utf8PathBuf {
+        Utf8PathBuf::from(format!("{}{}", target_path, DOWNLOAD_METADATA_SUFFIX))
+    }
+
+    /// Loads the metadata of an existing hashtable downloaded from the same url
+    fn load(target_path: &Utf8Path, url: &str) -> Option<Self> {
+        if !target_path.is_file() {
+            return None;
+        }
+
+        let contents = fs::read_to_string(Self::path(target_path).as_std_path()).ok()?;
+        match serde_json::from_str::<Self>(&contents) {
+            Ok(metadata) if metadata.url == url => Some(metadata),
+            Ok(_) => None,
+            Err(error) => {
+                tracing::debug!(
+                    "ignoring invalid download metadata of {} ({})",
+                    target_path,
+                    error
+                );
+                None
+            }
+        }
+    }
+
+    fn save(&self, target_path: &Utf8Path) -> Result<()> {
+        let path = Self::path(target_path);
+        fs::write(path.as_std_path(), serde_json::to_string_pretty(self)?)
+            .map_err(|e| eyre!("Failed to write {}: {}", path, e))
+    }
+
+    /// Forgets the validators of a hashtable that was replaced by other means than a download
+    fn remove(target_path: &Utf8Path) -> Result<()> {
+        match fs::remove_file(Self::path(target_path).as_std_path()) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Downloads a hashtable unless it is up to date, in which case `None` is returned
+fn download_file_with_progress(
+    url: &str,
+    filename: &str,
+    target_dir: &Utf8Path,
+) -> Result<Option<InstalledHashtable>> {
+    let target_path = target_dir.join(filename);
+
+    // Only download the file again if it changed since the last download
+    let mut request = ureq::get(url);
+    if let Some(metadata) = DownloadMetadata::load(&target_path, url) {
+        if let Some(etag) = &metadata.etag {
+            request = request.set("If-None-Match", etag);
+        }
+        if let Some(last_modified) = &metadata.last_modified {
+            request = request.set("If-Modified-Since", last_modified);
+        }
+    }
+
+    let response = request
+        .call()
+        .map_err(|e| eyre!("Failed to download {}: {}", filename, e))?;
+    if response.status() == 304 {
+        tracing::info!("{} is up to date", filename);
+        return Ok(None);
+    }
+
+    let metadata = DownloadMetadata {
+        url: url.to_string(),
+        etag: response.header("ETag").map(str::to_string),
+        last_modified: response.header("Last-Modified").map(str::to_string),
+    };
+    // Get content length for progress bar (if available)
+    let content_length: Option<u64> = response
+        .header("Content-Length")
+        .and_then(|s| s.parse().ok());
+
+    let installed = install_hashtable(filename, target_dir, content_length, |writer| {
+        writer
+            .copy_from(&mut response.into_reader())
+            .map_err(|e| eyre!("Failed to download {}: {}", filename, e))
+    })?;
+    metadata.save(&installed.path)?;
+    Ok(Some(installed))
+}
+
+/// A hashtable that replaced its previous version
+struct InstalledHashtable {
+    path: Utf8PathBuf,
+    /// Hashes that were not in the previous version
+    new_hash_count: usize,
+}
+
+/// Writes a hashtable next to its target and only replaces the target once complete, so a failed
+/// download or import never leaves a truncated hashtable behind. Reports how many hashes the new
+/// hashtable adds.
+fn install_hashtable(
+    filename: &str,
+    target_dir: &Utf8Path,
+    total: Option<u64>,
+    write: impl FnOnce(&mut HashtableWriter) -> Result<()>,
+) -> Result<InstalledHashtable> {
+    let target_path = target_dir.join(filename);
+    let mut partial_file = PartialFile::create(&target_path)?;
+    let written = write_partial(filename, partial_file.file(), total, write)?;
+
+    let known_hashes = match target_path.is_file() {
+        true => read_hashes(&target_path)?,
+        false => HashSet::new(),
+    };
+    let hashes = read_hashes(partial_file.partial_path())?;
+    let new_hash_count = hashes.difference(&known_hashes).count();
+
+    partial_file
+        .commit()
+        .map_err(|e| eyre!("Failed to replace {}: {:#}", target_path, e))?;
+
+    tracing::info!(
+        "Saved {} ({} bytes, {} new hashes, {} total)",
+        hyperlink_path(&target_path),
+        written,
+        new_hash_count,
+        hashes.len()
+    );
+    Ok(InstalledHashtable {
+        path: target_path,
+        new_hash_count,
+    })
+}
+
+/// Writes the partial file with a progress bar, returns the number of bytes written
+fn write_partial(
+    filename: &str,
+    file: &mut File,
+    total: Option<u64>,
+    write: impl FnOnce(&mut HashtableWriter) -> Result<()>,
+) -> Result<u64> {
+    // Create a tracing span for the progress bar
+    let span = tracing::info_span!("download", file = %filename);
+    let _entered = span.enter();
+
+    if let Some(total) = total {
+        span.pb_set_style(
+            &indicatif::ProgressStyle::with_template(
+                "{msg} {wide_bar:40.cyan/blue} {bytes}/{total_bytes} ({bytes_per_sec})",
+            )
+            .unwrap(),
+        );
+        span.pb_set_length(total);
+    } else {
+        span.pb_set_style(
+            &indicatif::ProgressStyle::with_template("{msg} {bytes} downloaded ({bytes_per_sec})")
+                .unwrap(),
+        );
+    }
+    span.pb_set_message(filename);
+
+    let mut writer = HashtableWriter {
+        file,
+        written: 0,
+        span: &span,
+    };
+    write(&mut writer)?;
+    writer.file.flush()?;
+
+    if let Some(total) = total {
+        if writer.written != total {
+            return Err(eyre!(
+                "Writing {} ended after {} of {} bytes",
+                filename,
+                writer.written,
+                total
+            ));
+        }
+    }
+
+    Ok(writer.written)
+}
+
+/// Writes to a partial hashtable file while updating its progress bar
+struct HashtableWriter<'a> {
+    file: &'a mut File,
+    written: u64,
+    span: &'a Span,
+}
+
+impl HashtableWriter<'_> {
+    fn copy_from(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut buffer = [0u8; DOWNLOAD_BUFFER_SIZE];
+        loop {
+            let bytes_read = reader.read(&mut buffer)?;
+            if bytes_read == 0 {
+                return Ok(());
+            }
+
+            self.file.write_all(&buffer[..bytes_read])?;
+            self.written += bytes_read as u64;
+            self.span.pb_set_position(self.written);
+        }
+    }
+}
+
+/// Reads the hashes of a hashtable, to count the ones a download added
+fn read_hashes(path: &Utf8Path) -> Result<HashSet<u64>> {
+    let mut hashes = HashSet::new();
+    // Bin hashes have fewer digits, so they parse as wad hashes as well
+    for_each_hashtable_entry(path, WAD_HASH_DIGITS, false, |hash, _| {
+        hashes.insert(hash);
+    })?;
+
+    Ok(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader},
+        net::TcpListener,
+        thread::{self, JoinHandle},
+    };
+
+    const HASHTABLE: &str = "0000000000000001 data/a.bin\n0000000000000002 data/b.bin\n";
+
+    /// Answers one connection per response, returns the url and the received request headers
+    fn serve(responses: Vec<Vec<u8>>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hashes.game.txt", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    request.push_str(&line);
+                }
+                requests.push(request);
+
+                stream.write_all(&response).unwrap();
+                // Dropping the stream closes the connection, ending short bodies early
+            }
+            requests
+        });
+
+        (url, server)
+    }
+
+    fn ok_response(body: &str, content_length: usize) -> Vec<u8> {
+        format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Length: {}\r\n\
+             ETag: \"v1\"\r\n\
+             Last-Modified: Wed, 21 Oct 2015 07:28:00 GMT\r\n\
+             Connection: close\r\n\r\n{}",
+            content_length, body
+        )
+        .into_bytes()
+    }
+
+    fn not_modified_response() -> Vec<u8> {
+        b"HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n".to_vec()
+    }
+
+    fn file_names(dir: &Utf8Path) -> Vec<String> {
+        let mut names = fs::read_dir(dir.as_std_path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn download_saves_validators_and_skips_unchanged_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = Utf8Path::from_path(temp_dir.path()).unwrap();
+        let (url, server) = serve(vec![
+            ok_response(HASHTABLE, HASHTABLE.len()),
+            not_modified_response(),
+        ]);
+
+        let installed = download_file_with_progress(&url, "hashes.game.txt", dir)
+            .unwrap()
+            .expect("first download installs the hashtable");
+        assert_eq!(installed.new_hash_count, 2);
+        let target_path = dir.join("hashes.game.txt");
+        assert_eq!(fs::read_to_string(&target_path).unwrap(), HASHTABLE);
+        let metadata = DownloadMetadata::load(&target_path, &url).unwrap();
+        assert_eq!(metadata.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(
+            metadata.last_modified.as_deref(),
+            Some("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+
+        let modified = fs::metadata(&target_path).unwrap().modified().unwrap();
+        assert!(download_file_with_progress(&url, "hashes.game.txt", dir)
+            .unwrap()
+            .is_none());
+        assert_eq!(fs::read_to_string(&target_path).unwrap(), HASHTABLE);
+        assert_eq!(
+            fs::metadata(&target_path).unwrap().modified().unwrap(),
+            modified
+        );
+
+        let requests = server.join().unwrap();
+        assert!(!requests[0].to_lowercase().contains("if-none-match"));
+        assert!(requests[1].to_lowercase().contains("if-none-match: \"v1\""));
+        assert!(requests[1]
+            .to_lowercase()
+            .contains("if-modified-since: wed, 21 oct 2015 07:28:00 gmt"));
+        assert_eq!(
+            file_names(dir),
+            ["hashes.game.txt", "hashes.game.txt.wadtools-meta"]
+        );
+    }
+
+    #[test]
+    fn download_counts_new_hashes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = Utf8Path::from_path(temp_dir.path()).unwrap();
+        fs::write(dir.join("hashes.game.txt"), "0000000000000001 data/a.bin\n").unwrap();
+        let updated = format!("{}0000000000000003 data/c.bin\n", HASHTABLE);
+        let (url, server) = serve(vec![ok_response(&updated, updated.len())]);
+
+        let installed = download_file_with_progress(&url, "hashes.game.txt", dir)
+            .unwrap()
+            .unwrap();
+        assert_eq!(installed.new_hash_count, 2);
+        assert_eq!(
+            fs::read_to_string(dir.join("hashes.game.txt")).unwrap(),
+            updated
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn truncated_download_keeps_previous_hashtable() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = Utf8Path::from_path(temp_dir.path()).unwrap();
+        let previous = "0000000000000001 data/a.bin\n";
+        fs::write(dir.join("hashes.game.txt"), previous).unwrap();
+        let (url, server) = serve(vec![ok_response(HASHTABLE, HASHTABLE.len() + 100)]);
+
+        assert!(download_file_with_progress(&url, "hashes.game.txt", dir).is_err());
+        server.join().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("hashes.game.txt")).unwrap(),
+            previous
+        );
+        assert_eq!(file_names(dir), ["hashes.game.txt"]);
+    }
+}
@@ -0,0 +1,337 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use eyre::{eyre, Context};
+use league_toolkit::{
+    file::LeagueFileKind,
+    wad::{FileExt as _, Wad, WadChunkCompression},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::BufWriter,
+};
+use tracing_indicatif::span_ext::IndicatifSpanExt;
+use tracing_indicatif::style::ProgressStyle;
+
+use crate::utils::{
+    default_hashtable_dir, parse_chunk_path_or_hash, truncate_middle, PartialFile, WadHashtable,
+    WadWriter,
+};
+
+use super::PackCompression;
+
+const MAX_LOG_PATH_LEN: usize = 120;
+
+/// A `<path|hash>=<file>` pair passed to `--add`/`--replace`
+#[derive(Debug, Clone)]
+pub struct ChunkSource {
+    pub chunk: String,
+    pub file: Utf8PathBuf,
+}
+
+pub fn parse_chunk_source(s: &str) -> Result<ChunkSource, String> {
+    let (chunk, file) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Expected <PATH>=<FILE>, got: {}", s))?;
+    if chunk.is_empty() || file.is_empty() {
+        return Err(format!("Expected <PATH>=<FILE>, got: {}", s));
+    }
+
+    Ok(ChunkSource {
+        chunk: chunk.to_string(),
+        file: Utf8PathBuf::from(file),
+    })
+}
+
+pub struct EditArgs {
+    pub input: String,
+    pub output: Option<String>,
+    pub add: Vec<ChunkSource>,
+    pub replace: Vec<ChunkSource>,
+    pub remove: Vec<String>,
+    pub compression: PackCompression,
+    pub hashtable: Option<String>,
+    pub hashtable_dir: Option<String>,
+    pub strict_hashtables: bool,
+}
+
+pub fn edit(args: EditArgs) -> eyre::Result<()> {
+    let source = File::open(&args.input)?;
+    let mut wad = Wad::mount(&source)?;
+
+    let added_path_hashes = args
+        .add
+        .iter()
+        .map(|source| parse_chunk_path_or_hash(&source.chunk));
+    let mut hashtable =
+        WadHashtable::with_path_hashes(wad.chunks().keys().copied().chain(added_path_hashes))?;
+    hashtable.set_strict(args.strict_hashtables);
+    if let Some(dir_override) = &args.hashtable_dir {
+        hashtable.add_from_dir(Utf8Path::new(dir_override))?;
+    } else if let Some(dir) = default_hashtable_dir() {
+        hashtable.add_from_dir(dir)?;
+    }
+    if let Some(hashtable_path) = &args.hashtable {
+        tracing::info!("loading hashtable from {}", hashtable_path);
+        hashtable.add_from_file(Utf8Path::new(hashtable_path))?;
+    }
+
+    let (mut decoder, chunks) = wad.decode();
+
+    // Resolve and validate all operations up front so we never write a half-edited wad
+    let mut removals = HashSet::<u64>::new();
+    for chunk in &args.remove {
+        let path_hash = parse_chunk_path_or_hash(chunk);
+        if !chunks.contains_key(&path_hash) {
+            return Err(eyre!("cannot remove missing chunk: {}", chunk));
+        }
+        removals.insert(path_hash);
+    }
+
+    let mut replacements = HashMap::<u64, Utf8PathBuf>::new();
+    for source in &args.replace {
+        let path_hash = parse_chunk_path_or_hash(&source.chunk);
+        if !chunks.contains_key(&path_hash) {
+            return Err(eyre!("cannot replace missing chunk: {}", source.chunk));
+        }
+        if removals.contains(&path_hash) || replacements.contains_key(&path_hash) {
+            return Err(eyre!("chunk is edited more than once: {}", source.chunk));
+        }
+        replacements.insert(path_hash, source.file.clone());
+    }
+
+    let mut additions = Vec::<(u64, Utf8PathBuf)>::new();
+    for source in &args.add {
+        let path_hash = parse_chunk_path_or_hash(&source.chunk);
+        if chunks.contains_key(&path_hash) {
+            return Err(eyre!(
+                "cannot add existing chunk (use --replace instead): {}",
+                source.chunk
+            ));
+        }
+        if additions.iter().any(|(hash, _)| *hash == path_hash) {
+            return Err(eyre!("chunk is added more than once: {}", source.chunk));
+        }
+        additions.push((path_hash, source.file.clone()));
+    }
+
+    for file in replacements
+        .values()
+        .chain(additions.iter().map(|(_, file)| file))
+    {
+        let metadata = fs::metadata(file.as_std_path())
+            .wrap_err(format!("failed to read file (path: {})", file))?;
+        if !metadata.is_file() {
+            return Err(eyre!("not a file: {}", file));
+        }
+    }
+
+    let input_path = Utf8Path::new(&args.input);
+    let output_path = args
+        .output
+        .as_deref()
+        .map(Utf8PathBuf::from)
+        .unwrap_or_else(|| input_path.to_path_buf());
+    // Write next to the output and swap it in at the end, so the wad we are still reading from
+    // is never overwritten and a failed edit leaves nothing behind
+    let mut output_file = PartialFile::create(&output_path)?;
+
+    let compression = args.compression.to_chunk_compression();
+    let chunk_count = chunks.len() - removals.len() + additions.len();
+    let mut writer = WadWriter::new(BufWriter::new(output_file.file()), chunk_count)?;
+
+    let span = tracing::info_span!("edit", total = chunk_count);
+    let _entered = span.enter();
+    span.pb_set_style(
+        &ProgressStyle::with_template("{wide_bar:40.cyan/blue} {pos}/{len} \n {spinner} {msg}")
+            .unwrap(),
+    );
+    span.pb_set_length(chunk_count as u64);
+    span.pb_set_message("Writing chunks");
+    span.pb_set_finish_message("Writing complete");
+
+    let mut ordered_chunks = chunks.values().collect::<Vec<_>>();
+    ordered_chunks.sort_by_key(|chunk| chunk.path_hash);
+
+    let mut copied_count = 0;
+    for chunk in ordered_chunks {
+        if removals.contains(&chunk.path_hash) {
+            continue;
+        }
+
+        let chunk_path = hashtable.resolve_path(chunk.path_hash);
+        span.pb_set_message(&truncate_middle(&chunk_path, MAX_LOG_PATH_LEN));
+
+        match replacements.get(&chunk.path_hash) {
+            Some(file) => {
+                write_file_chunk(&mut writer, chunk.path_hash, file, compression)?;
+                tracing::info!("replaced {}", chunk_path);
+            }
+            None => {
+                let raw_data = decoder
+                    .load_chunk_raw(chunk)
+                    .wrap_err(format!("failed to read chunk (chunk_path: {})", chunk_path))?;
+                writer.write_raw_chunk(chunk, &raw_data)?;
+                copied_count += 1;
+            }
+        }
+        span.pb_inc(1);
+    }
+
+    for (path_hash, file) in &additions {
+        let chunk_path = hashtable.resolve_path(*path_hash);
+        span.pb_set_message(&truncate_middle(&chunk_path, MAX_LOG_PATH_LEN));
+
+        write_file_chunk(&mut writer, *path_hash, file, compression)?;
+        tracing::info!("added {}", chunk_path);
+        span.pb_inc(1);
+    }
+
+    writer.finish()?;
+    for path_hash in &removals {
+        tracing::info!("removed {}", hashtable.resolve_path(*path_hash));
+    }
+
+    drop(wad);
+    drop(source);
+    output_file.commit()?;
+
+    tracing::info!(
+        "wrote {} ({} added, {} replaced, {} removed, {} copied)",
+        output_path,
+        additions.len(),
+        replacements.len(),
+        removals.len(),
+        copied_count
+    );
+
+    Ok(())
+}
+
+fn write_file_chunk(
+    writer: &mut WadWriter<BufWriter<&mut File>>,
+    path_hash: u64,
+    file: &Utf8Path,
+    compression: Option<WadChunkCompression>,
+) -> eyre::Result<()> {
+    let data =
+        fs::read(file.as_std_path()).wrap_err(format!("failed to read file (path: {})", file))?;
+    let compression = compression
+        .unwrap_or_else(|| LeagueFileKind::identify_from_bytes(&data).ideal_compression());
+
+    writer.write_chunk(path_hash, &data, compression)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::hash_chunk_path;
+    use league_toolkit::wad::WadChunk;
+    use std::io::{Cursor, Seek, SeekFrom};
+
+    const WAD_TOC_OFFSET: u64 = 272;
+
+    fn chunk_source(chunk: &str, file: &Utf8Path) -> ChunkSource {
+        ChunkSource {
+            chunk: chunk.to_string(),
+            file: file.to_path_buf(),
+        }
+    }
+
+    /// Writes a wad whose `shared` chunk points at the data of `duplicate`
+    fn write_source_wad(path: &Utf8Path, chunks: &[(&str, &[u8])], shared: (&str, &str)) {
+        let mut writer = WadWriter::new(Cursor::new(Vec::new()), chunks.len()).unwrap();
+        let mut written = chunks
+            .iter()
+            .map(|(chunk_path, data)| {
+                writer
+                    .write_chunk(hash_chunk_path(chunk_path), data, WadChunkCompression::Zstd)
+                    .unwrap()
+            })
+            .collect::<Vec<WadChunk>>();
+        let mut cursor = writer.finish().unwrap();
+
+        let duplicate = written
+            .iter()
+            .find(|chunk| chunk.path_hash == hash_chunk_path(shared.1))
+            .copied()
+            .unwrap();
+        for chunk in &mut written {
+            if chunk.path_hash == hash_chunk_path(shared.0) {
+                chunk.data_offset = duplicate.data_offset;
+            }
+        }
+        written.sort_by_key(|chunk| chunk.path_hash);
+        cursor.seek(SeekFrom::Start(WAD_TOC_OFFSET)).unwrap();
+        for chunk in &written {
+            chunk.write_v3_4(&mut cursor).unwrap();
+        }
+
+        fs::write(path, cursor.into_inner()).unwrap();
+    }
+
+    #[test]
+    fn edit_round_trips_chunks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(temp_dir.path()).unwrap();
+        let hashtable_dir = root.join("hashes");
+        fs::create_dir(&hashtable_dir).unwrap();
+
+        let input_path = root.join("Test.wad.client");
+        write_source_wad(
+            &input_path,
+            &[
+                ("data/untouched.bin", b"untouched"),
+                ("data/replaced.bin", b"old contents"),
+                ("data/removed.bin", b"removed"),
+                ("data/duplicate.bin", b"shared contents"),
+                ("data/shared.bin", b"shared contents"),
+            ],
+            ("data/shared.bin", "data/duplicate.bin"),
+        );
+        let replacement = root.join("replaced.bin");
+        fs::write(&replacement, b"new contents").unwrap();
+        let addition = root.join("added.bin");
+        fs::write(&addition, b"added contents").unwrap();
+
+        let output_path = root.join("Edited.wad.client");
+        edit(EditArgs {
+            input: input_path.to_string(),
+            output: Some(output_path.to_string()),
+            add: vec![chunk_source("data/added.bin", &addition)],
+            replace: vec![chunk_source("data/replaced.bin", &replacement)],
+            remove: vec!["data/removed.bin".to_string()],
+            compression: PackCompression::Auto,
+            hashtable: None,
+            hashtable_dir: Some(hashtable_dir.to_string()),
+            strict_hashtables: false,
+        })
+        .unwrap();
+
+        let source = File::open(output_path.as_std_path()).unwrap();
+        let mut wad = Wad::mount(&source).unwrap();
+        let (mut decoder, chunks) = wad.decode();
+        assert_eq!(chunks.len(), 5);
+        assert!(!chunks.contains_key(&hash_chunk_path("data/removed.bin")));
+
+        for (chunk_path, contents) in [
+            ("data/untouched.bin", &b"untouched"[..]),
+            ("data/replaced.bin", b"new contents"),
+            ("data/added.bin", b"added contents"),
+            ("data/duplicate.bin", b"shared contents"),
+            ("data/shared.bin", b"shared contents"),
+        ] {
+            let chunk = chunks
+                .get(&hash_chunk_path(chunk_path))
+                .unwrap_or_else(|| panic!("missing chunk for {}", chunk_path));
+            let data = decoder.load_chunk_decompressed(chunk).unwrap();
+            assert_eq!(&data[..], contents, "{}", chunk_path);
+        }
+
+        // Chunks sharing their data keep sharing it
+        let duplicate = chunks[&hash_chunk_path("data/duplicate.bin")];
+        let shared = chunks[&hash_chunk_path("data/shared.bin")];
+        assert_eq!(duplicate.data_offset, shared.data_offset);
+        assert_eq!(duplicate.checksum, shared.checksum);
+    }
+}
@@ -1,65 +1,311 @@
 use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::owo_colors::OwoColorize;
-use std::fs::File;
+use eyre::{eyre, Context};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    thread,
+};
 
-use league_toolkit::{file::LeagueFileKind, wad::Wad};
+use league_toolkit::{
+    file::LeagueFileKind,
+    wad::{Wad, WadChunk},
+};
 
 use crate::{
-    extractor::Extractor,
-    utils::{create_filter_pattern, default_hashtable_dir, WadHashtable},
+    extract_manifest::ExtractManifest,
+    extractor::{ChunkDirectories, Extractor},
+    utils::{
+        collect_input_wads, collect_wad_path_hashes, create_filter_pattern, default_hashtable_dir,
+        WadHashtable,
+    },
 };
 use convert_case::{Case, Casing};
 
+/// What to do when several wads contain a chunk with the same path but different contents
+/// while merging them into one tree
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum ConflictPolicy {
+    /// Keep the chunk from the first wad (in path order)
+    #[default]
+    Skip,
+    /// Overwrite with the chunk from the last wad (in path order)
+    Overwrite,
+    /// Abort the extraction
+    Error,
+}
+
 pub struct ExtractArgs {
-    pub input: String,
+    /// Wad files, globs or directories to extract
+    pub input: Vec<String>,
     pub output: Option<String>,
     pub hashtable: Option<String>,
     pub filter_type: Option<Vec<LeagueFileKind>>,
     pub pattern: Option<String>,
     pub hashtable_dir: Option<String>,
+    pub strict_hashtables: bool,
+    /// Number of worker threads, defaults to the number of available cores
+    pub jobs: Option<usize>,
+    /// Extract all wads into the output directory instead of one subfolder per wad
+    pub merge: bool,
+    pub on_conflict: ConflictPolicy,
+    /// Only extract chunks that changed since the last incremental extraction
+    pub incremental: bool,
+}
+
+/// Manifest and bookkeeping of an output directory during an incremental extraction
+struct IncrementalState {
+    manifest: ExtractManifest,
+    /// Path hashes of all chunks of the wads extracted into the directory
+    present_chunks: HashSet<u64>,
+    unchanged_count: usize,
 }
 
 pub fn extract(args: ExtractArgs) -> eyre::Result<()> {
-    let source = File::open(&args.input)?;
+    let wads = collect_input_wads(&args.input)?;
+    if wads.is_empty() {
+        return Err(eyre!(
+            "No wad files found in input: {}",
+            args.input.join(", ")
+        ));
+    }
 
-    let mut wad = Wad::mount(&source)?;
+    // Check the arguments before loading the hashtable, which can take a while
+    if args.merge && args.output.is_none() {
+        return Err(eyre!("--merge requires an --output directory"));
+    }
+    if !args.merge && wads.len() > 1 {
+        let mut folders = HashSet::new();
+        for wad in &wads {
+            let folder = wad_folder(&wad.relative_path);
+            if !folders.insert(folder.clone()) {
+                return Err(eyre!(
+                    "Multiple input wads extract into the same folder: {} (use --merge to extract them into one tree)",
+                    folder
+                ));
+            }
+        }
+    }
 
-    let (mut decoder, chunks) = wad.decode();
+    let filter_pattern = create_filter_pattern(args.pattern)?;
 
-    let mut hashtable = WadHashtable::new()?;
+    let path_hashes = collect_wad_path_hashes(wads.iter().map(|wad| wad.path.as_path()))?;
+    let mut hashtable = WadHashtable::with_path_hashes(path_hashes)?;
+    hashtable.set_strict(args.strict_hashtables);
     if let Some(dir_override) = &args.hashtable_dir {
         hashtable.add_from_dir(Utf8Path::new(dir_override))?;
     } else if let Some(dir) = default_hashtable_dir() {
         hashtable.add_from_dir(dir)?;
     }
-    if let Some(hashtable_path) = args.hashtable {
+    if let Some(hashtable_path) = &args.hashtable {
         tracing::info!("loading hashtable from {}", hashtable_path);
-        hashtable.add_from_file(&File::open(&hashtable_path)?)?;
+        hashtable.add_from_file(Utf8Path::new(hashtable_path))?;
     }
 
-    let mut extractor = Extractor::new(&mut decoder, &hashtable);
+    let jobs = args
+        .jobs
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |jobs| jobs.get()));
 
-    let filter_pattern = create_filter_pattern(args.pattern)?;
+    // Path hash -> checksum of every chunk already extracted into the merged tree
+    let mut merged_chunks = HashMap::<u64, u64>::new();
+    let mut incremental_states = HashMap::<Utf8PathBuf, IncrementalState>::new();
+    let mut extracted_count = 0;
+    for (i, input_wad) in wads.iter().enumerate() {
+        if wads.len() > 1 {
+            tracing::info!("extracting {} ({}/{})", input_wad.path, i + 1, wads.len());
+        }
+
+        let source = File::open(input_wad.path.as_std_path())
+            .wrap_err(format!("failed to open wad: {}", input_wad.path))?;
+        let wad =
+            Wad::mount(&source).wrap_err(format!("failed to mount wad: {}", input_wad.path))?;
+
+        let output_dir: Utf8PathBuf = match (&args.output, args.merge || wads.len() == 1) {
+            (Some(path), true) => Utf8PathBuf::from(path.as_str()),
+            (Some(path), false) => Utf8Path::new(path).join(wad_folder(&input_wad.relative_path)),
+            (None, _) => {
+                // Construct sibling dir named after input file (without extension)
+                let parent = input_wad.path.parent().unwrap_or(Utf8Path::new("."));
+                let stem = input_wad.path.file_stem().unwrap_or("extracted");
+                parent.join(stem)
+            }
+        };
 
-    extractor.set_filter_pattern(filter_pattern);
-    let output_dir: Utf8PathBuf = match &args.output {
-        Some(path) => Utf8PathBuf::from(path.as_str()),
-        None => {
-            // Construct sibling dir named after input file (without extension)
-            let input_path = Utf8Path::new(&args.input);
-            let parent = input_path.parent().unwrap_or(Utf8Path::new("."));
-            let stem = input_path.file_stem().unwrap_or("extracted");
-            parent.join(stem)
+        let mut chunks = match args.merge {
+            true => resolve_merge_conflicts(
+                wad.chunks(),
+                &mut merged_chunks,
+                args.on_conflict,
+                &input_wad.path,
+                &hashtable,
+            )?,
+            false => wad.chunks().clone(),
+        };
+
+        let chunk_directories = ChunkDirectories::collect(wad.chunks(), &hashtable);
+        let mut incremental_state = match args.incremental {
+            true => {
+                if !incremental_states.contains_key(&output_dir) {
+                    let state = IncrementalState {
+                        manifest: ExtractManifest::load(&output_dir)?,
+                        present_chunks: HashSet::new(),
+                        unchanged_count: 0,
+                    };
+                    incremental_states.insert(output_dir.clone(), state);
+                }
+                incremental_states.get_mut(&output_dir)
+            }
+            false => None,
+        };
+        if let Some(state) = &mut incremental_state {
+            state.present_chunks.extend(wad.chunks().keys());
+
+            let chunk_count = chunks.len();
+            chunks.retain(|_, chunk| {
+                !is_chunk_up_to_date(
+                    &state.manifest,
+                    chunk,
+                    &chunk_directories,
+                    &hashtable,
+                    &output_dir,
+                )
+            });
+            state.unchanged_count += chunk_count - chunks.len();
+
+            // The file of a chunk that gets renamed can be in the way of a new directory
+            for path_hash in chunks.keys() {
+                if state
+                    .manifest
+                    .output_path(*path_hash)
+                    .is_some_and(|path| chunk_directories.contains(path))
+                {
+                    state.manifest.remove(*path_hash, &output_dir)?;
+                }
+            }
         }
-    };
-    let extracted_count =
-        extractor.extract_chunks(chunks, &output_dir, args.filter_type.as_deref())?;
 
-    tracing::info!("extracted {} chunks :)", extracted_count);
+        let mut extractor = Extractor::new(&input_wad.path, &hashtable);
+        extractor.set_jobs(jobs);
+        extractor.set_filter_pattern(filter_pattern.clone());
+        let extracted_chunks = extractor.extract_chunks(
+            &chunks,
+            &chunk_directories,
+            &output_dir,
+            args.filter_type.as_deref(),
+        )?;
+        extracted_count += extracted_chunks.len();
+
+        if let Some(state) = incremental_state {
+            state.manifest.record(&extracted_chunks, &output_dir)?;
+            // Saved after every wad, so an interrupted run keeps track of the files it wrote
+            state.manifest.save(&output_dir)?;
+        }
+    }
+
+    // Only clean up once every wad extracting into the directory is done, chunks can move
+    // between wads that are merged into the same tree
+    for (output_dir, mut state) in incremental_states {
+        let removed_count = state
+            .manifest
+            .remove_missing(&state.present_chunks, &output_dir)?;
+        state.manifest.save(&output_dir)?;
+        tracing::info!(
+            "{}: {} chunks unchanged, {} removed",
+            output_dir,
+            state.unchanged_count,
+            removed_count
+        );
+    }
+
+    match wads.len() {
+        1 => tracing::info!("extracted {} chunks :)", extracted_count),
+        wad_count => tracing::info!(
+            "extracted {} chunks from {} wads :)",
+            extracted_count,
+            wad_count
+        ),
+    }
 
     Ok(())
 }
 
+/// Whether the chunk was extracted before, hasn't changed since and would be written to the
+/// same file again
+fn is_chunk_up_to_date(
+    manifest: &ExtractManifest,
+    chunk: &WadChunk,
+    chunk_directories: &ChunkDirectories,
+    hashtable: &WadHashtable,
+    output_dir: &Utf8Path,
+) -> bool {
+    let Some(output_path) = manifest.output_path(chunk.path_hash) else {
+        return false;
+    };
+    let chunk_path = hashtable.resolve_path(chunk.path_hash);
+
+    manifest.is_up_to_date(chunk, output_dir)
+        && chunk_directories.keeps_output_path(
+            chunk,
+            Utf8Path::new(chunk_path.as_ref()),
+            output_path,
+            output_dir,
+        )
+}
+
+/// Drops the chunks that were already extracted into the merged tree by a previous wad,
+/// applying the conflict policy to the ones whose contents differ
+fn resolve_merge_conflicts(
+    chunks: &HashMap<u64, WadChunk>,
+    merged_chunks: &mut HashMap<u64, u64>,
+    on_conflict: ConflictPolicy,
+    wad_path: &Utf8Path,
+    hashtable: &WadHashtable,
+) -> eyre::Result<HashMap<u64, WadChunk>> {
+    let mut selected_chunks = HashMap::with_capacity(chunks.len());
+    for (path_hash, chunk) in chunks {
+        let Some(checksum) = merged_chunks.insert(*path_hash, chunk.checksum) else {
+            selected_chunks.insert(*path_hash, *chunk);
+            continue;
+        };
+        if checksum == chunk.checksum {
+            continue;
+        }
+
+        match on_conflict {
+            ConflictPolicy::Skip => {
+                merged_chunks.insert(*path_hash, checksum);
+                tracing::debug!(
+                    "skipping conflicting chunk (chunk_path: {}, wad: {})",
+                    hashtable.resolve_path(*path_hash),
+                    wad_path
+                );
+            }
+            ConflictPolicy::Overwrite => {
+                selected_chunks.insert(*path_hash, *chunk);
+            }
+            ConflictPolicy::Error => {
+                return Err(eyre!(
+                    "conflicting chunk (chunk_path: {}, wad: {})",
+                    hashtable.resolve_path(*path_hash),
+                    wad_path
+                ));
+            }
+        }
+    }
+
+    Ok(selected_chunks)
+}
+
+/// The subfolder a wad is extracted into, named after the wad file (without extension).
+/// Keeps the layout of wads found in a directory by passing their relative path.
+fn wad_folder(relative_path: &Utf8Path) -> Utf8PathBuf {
+    let stem = relative_path.file_stem().unwrap_or("extracted");
+    match relative_path.parent() {
+        Some(parent) => parent.join(stem),
+        None => Utf8PathBuf::from(stem),
+    }
+}
+
 pub fn print_supported_filters() {
     println!("Supported filter types (name -> description [extension]):");
     for kind in LeagueFileKind::iter().collect::<Vec<_>>() {
@@ -73,3 +319,96 @@ pub fn print_supported_filters() {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{format_chunk_path_hash, hash_chunk_path, WadWriter};
+    use league_toolkit::wad::WadChunkCompression;
+    use std::{fs, io::BufWriter};
+
+    fn write_wad(path: &Utf8Path, chunks: &[(&str, &[u8])]) {
+        let file = BufWriter::new(File::create(path.as_std_path()).unwrap());
+        let mut writer = WadWriter::new(file, chunks.len()).unwrap();
+        for (chunk_path, data) in chunks {
+            writer
+                .write_chunk(hash_chunk_path(chunk_path), data, WadChunkCompression::Zstd)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn extract_incremental(root: &Utf8Path, wad_path: &Utf8Path, output_dir: &Utf8Path) {
+        extract(ExtractArgs {
+            input: vec![wad_path.to_string()],
+            output: Some(output_dir.to_string()),
+            hashtable: Some(root.join("hashes.game.txt").to_string()),
+            filter_type: None,
+            pattern: None,
+            hashtable_dir: Some(root.join("hashes").to_string()),
+            strict_hashtables: false,
+            jobs: Some(2),
+            merge: false,
+            on_conflict: ConflictPolicy::Skip,
+            incremental: true,
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn incremental_extract_renames_chunks_that_start_colliding() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(temp_dir.path()).unwrap();
+        fs::create_dir(root.join("hashes")).unwrap();
+        let chunk_paths = ["data/ui.bin", "data/ui.bin/child.txt", "data/other.bin"];
+        let hashtable = chunk_paths
+            .iter()
+            .map(|path| {
+                format!(
+                    "{} {}\n",
+                    format_chunk_path_hash(hash_chunk_path(path)),
+                    path
+                )
+            })
+            .collect::<String>();
+        fs::write(root.join("hashes.game.txt"), hashtable).unwrap();
+
+        let wad_path = root.join("Test.wad.client");
+        let output_dir = root.join("out");
+        write_wad(
+            &wad_path,
+            &[("data/ui.bin", b"ui"), ("data/other.bin", b"other")],
+        );
+        extract_incremental(root, &wad_path, &output_dir);
+        assert_eq!(fs::read(output_dir.join("data/ui.bin")).unwrap(), b"ui");
+
+        // Unchanged chunks are not written again
+        fs::write(output_dir.join("data/other.bin"), b"kept").unwrap();
+
+        // `data/ui.bin` becomes a directory, so the unchanged chunk moves out of its way
+        write_wad(
+            &wad_path,
+            &[
+                ("data/ui.bin", b"ui"),
+                ("data/ui.bin/child.txt", b"child"),
+                ("data/other.bin", b"other"),
+            ],
+        );
+        extract_incremental(root, &wad_path, &output_dir);
+        assert_eq!(fs::read(output_dir.join("data/ui.bin.ltk")).unwrap(), b"ui");
+        assert_eq!(
+            fs::read(output_dir.join("data/ui.bin/child.txt")).unwrap(),
+            b"child"
+        );
+        assert_eq!(
+            fs::read(output_dir.join("data/other.bin")).unwrap(),
+            b"kept"
+        );
+
+        let manifest = ExtractManifest::load(&output_dir).unwrap();
+        assert_eq!(
+            manifest.output_path(hash_chunk_path("data/ui.bin")),
+            Some(Utf8Path::new("data/ui.bin.ltk"))
+        );
+    }
+}
@@ -0,0 +1,436 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use eyre::{eyre, Context};
+use rayon::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write as _,
+    fs, thread,
+};
+use tracing_indicatif::span_ext::IndicatifSpanExt;
+use tracing_indicatif::style::ProgressStyle;
+
+use crate::utils::{
+    append_hashtable_entries, collect_input_wads, collect_wad_path_hashes, default_hashtable_dir,
+    format_chunk_path_hash, hash_normalized_chunk_path, normalize_chunk_path, WadHashtable,
+};
+
+/// Number of expansions hashed by a worker between progress updates
+const GUESS_BLOCK_SIZE: u64 = 64 * 1024;
+
+/// A named wordlist, used by a template through its `{name}` placeholder
+#[derive(Debug, Clone)]
+pub struct Wordlist {
+    pub name: String,
+    pub file: Utf8PathBuf,
+}
+
+pub fn parse_wordlist(s: &str) -> Result<Wordlist, String> {
+    let (name, file) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=FILE, got: {}", s))?;
+    if name.is_empty() || file.is_empty() {
+        return Err(format!("expected NAME=FILE, got: {}", s));
+    }
+
+    Ok(Wordlist {
+        name: name.to_string(),
+        file: Utf8PathBuf::from(file),
+    })
+}
+
+pub struct GuessArgs {
+    pub input: Vec<String>,
+    pub templates: Vec<String>,
+    pub wordlists: Vec<Wordlist>,
+    /// Hashtable file the hits are appended to, printed to stdout when not set
+    pub output: Option<String>,
+    pub hashtable: Option<String>,
+    pub hashtable_dir: Option<String>,
+    pub strict_hashtables: bool,
+    /// Number of worker threads, defaults to the number of available cores
+    pub jobs: Option<usize>,
+}
+
+/// A template split into segments, every expansion picks one choice per segment.
+/// Literals are segments with a single choice.
+struct PathTemplate {
+    /// Segments along with the number of expansions between two of their choices
+    segments: Vec<(TemplateSegment, u64)>,
+    expansion_count: u64,
+}
+
+enum TemplateSegment {
+    /// A literal, the words of a wordlist or alternatives
+    Choices(Vec<String>),
+    /// A number range, only formatted while expanding so it takes no memory whatever its size
+    Range { start: u64, len: u64, width: usize },
+}
+
+impl TemplateSegment {
+    fn len(&self) -> u64 {
+        match self {
+            TemplateSegment::Choices(choices) => choices.len() as u64,
+            TemplateSegment::Range { len, .. } => *len,
+        }
+    }
+}
+
+impl PathTemplate {
+    /// Writes the expansion with the given index into `path`, the last segment changes fastest
+    fn expand_into(&self, index: u64, path: &mut String) {
+        path.clear();
+        for (segment, stride) in &self.segments {
+            let choice = (index / stride) % segment.len();
+            match segment {
+                TemplateSegment::Choices(choices) => path.push_str(&choices[choice as usize]),
+                TemplateSegment::Range { start, width, .. } => {
+                    let _ = write!(path, "{:0width$}", start + choice, width = *width);
+                }
+            }
+        }
+    }
+}
+
+pub fn guess(args: GuessArgs) -> eyre::Result<()> {
+    let wads = collect_input_wads(&args.input)?;
+    if wads.is_empty() {
+        return Err(eyre!(
+            "No wad files found in input: {}",
+            args.input.join(", ")
+        ));
+    }
+
+    let mut wordlists = HashMap::<String, Vec<String>>::new();
+    for wordlist in &args.wordlists {
+        wordlists.insert(wordlist.name.clone(), read_wordlist(&wordlist.file)?);
+    }
+    let templates = args
+        .templates
+        .iter()
+        .map(|template| parse_template(template, &wordlists))
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    let path_hashes = collect_wad_path_hashes(wads.iter().map(|wad| wad.path.as_path()))?;
+    let mut hashtable = WadHashtable::with_path_hashes(path_hashes.iter().copied())?;
+    hashtable.set_strict(args.strict_hashtables);
+    if let Some(dir_override) = &args.hashtable_dir {
+        hashtable.add_from_dir(Utf8Path::new(dir_override))?;
+    } else if let Some(dir) = default_hashtable_dir() {
+        hashtable.add_from_dir(dir)?;
+    }
+    if let Some(hashtable_path) = &args.hashtable {
+        tracing::info!("loading hashtable from {}", hashtable_path);
+        hashtable.add_from_file(Utf8Path::new(hashtable_path))?;
+    }
+    // Don't append hits of a previous run again
+    if let Some(output) = &args.output {
+        if Utf8Path::new(output).is_file() {
+            hashtable.add_from_file(Utf8Path::new(output))?;
+        }
+    }
+
+    let unknown_hashes = path_hashes
+        .into_iter()
+        .filter(|path_hash| !hashtable.contains(*path_hash))
+        .collect::<HashSet<_>>();
+    if unknown_hashes.is_empty() {
+        tracing::info!("all chunk paths are already known :)");
+        return Ok(());
+    }
+
+    let expansion_count = templates
+        .iter()
+        .try_fold(0u64, |count, template| {
+            count.checked_add(template.expansion_count)
+        })
+        .ok_or_else(|| eyre!("templates expand to too many paths"))?;
+    tracing::info!(
+        "hashing {} paths against {} unknown chunk paths...",
+        expansion_count,
+        unknown_hashes.len()
+    );
+
+    let jobs = args
+        .jobs
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |jobs| jobs.get()));
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .wrap_err("failed to create thread pool")?;
+
+    let span = tracing::info_span!("guess", total = expansion_count);
+    span.pb_set_style(
+        &ProgressStyle::with_template("{wide_bar:40.cyan/blue} {pos}/{len} \n {spinner} {msg}")
+            .unwrap(),
+    );
+    span.pb_set_length(expansion_count);
+    span.pb_set_message("Hashing paths");
+    span.pb_set_finish_message("Hashing complete");
+
+    let mut hits = BTreeMap::<u64, String>::new();
+    span.in_scope(|| {
+        for template in &templates {
+            let block_count = template.expansion_count.div_ceil(GUESS_BLOCK_SIZE);
+            let template_hits = pool.install(|| {
+                (0..block_count)
+                    .into_par_iter()
+                    .flat_map_iter(|block| {
+                        let start = block * GUESS_BLOCK_SIZE;
+                        let end = (start + GUESS_BLOCK_SIZE).min(template.expansion_count);
+
+                        let mut path = String::new();
+                        let mut block_hits = Vec::new();
+                        for index in start..end {
+                            template.expand_into(index, &mut path);
+                            let path_hash = hash_normalized_chunk_path(&path);
+                            if unknown_hashes.contains(&path_hash) {
+                                block_hits.push((path_hash, path.clone()));
+                            }
+                        }
+
+                        span.pb_inc(end - start);
+                        block_hits
+                    })
+                    .collect::<Vec<_>>()
+            });
+
+            for (path_hash, path) in template_hits {
+                hits.entry(path_hash).or_insert(path);
+            }
+        }
+    });
+
+    match &args.output {
+        Some(output) => {
+            append_hashtable_entries(Utf8Path::new(output), &hits)?;
+            for (path_hash, path) in &hits {
+                tracing::info!("{} {}", format_chunk_path_hash(*path_hash), path);
+            }
+        }
+        None => {
+            for (path_hash, path) in &hits {
+                println!("{} {}", format_chunk_path_hash(*path_hash), path);
+            }
+        }
+    }
+
+    tracing::info!(
+        "guessed {} of {} unknown chunk paths",
+        hits.len(),
+        unknown_hashes.len()
+    );
+
+    Ok(())
+}
+
+/// Reads one word per line, skipping blank lines and duplicates
+fn read_wordlist(path: &Utf8Path) -> eyre::Result<Vec<String>> {
+    let contents = fs::read_to_string(path.as_std_path())
+        .wrap_err(format!("failed to read wordlist: {}", path))?;
+
+    let mut seen = HashSet::new();
+    let words = contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|word| !word.is_empty() && seen.insert(*word))
+        .map(normalize_chunk_path)
+        .collect::<Vec<_>>();
+    if words.is_empty() {
+        return Err(eyre!("wordlist is empty: {}", path));
+    }
+
+    Ok(words)
+}
+
+/// Splits a template into literals and placeholders:
+/// `{name}` (a wordlist), `{0..99}` (a number range, zero padded like `{00..99}`) and
+/// `{a,b,c}` (alternatives)
+fn parse_template(
+    template: &str,
+    wordlists: &HashMap<String, Vec<String>>,
+) -> eyre::Result<PathTemplate> {
+    let mut segments = Vec::<TemplateSegment>::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        if rest[..start].contains('}') {
+            return Err(eyre!("unmatched '}}' in template: {}", template));
+        }
+        if start > 0 {
+            segments.push(TemplateSegment::Choices(vec![normalize_chunk_path(
+                &rest[..start],
+            )]));
+        }
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| eyre!("unclosed placeholder in template: {}", template))?;
+        if rest[start + 1..end].contains('{') {
+            return Err(eyre!("nested placeholder in template: {}", template));
+        }
+
+        segments.push(
+            parse_placeholder(&rest[start + 1..end], wordlists)
+                .wrap_err(format!("invalid placeholder in template: {}", template))?,
+        );
+        rest = &rest[end + 1..];
+    }
+    if rest.contains('}') {
+        return Err(eyre!("unmatched '}}' in template: {}", template));
+    }
+    if !rest.is_empty() {
+        segments.push(TemplateSegment::Choices(vec![normalize_chunk_path(rest)]));
+    }
+
+    let mut strided_segments = Vec::with_capacity(segments.len());
+    let mut expansion_count = 1u64;
+    for segment in segments.into_iter().rev() {
+        let stride = expansion_count;
+        expansion_count = expansion_count
+            .checked_mul(segment.len())
+            .ok_or_else(|| eyre!("template expands to too many paths: {}", template))?;
+        strided_segments.push((segment, stride));
+    }
+    strided_segments.reverse();
+
+    Ok(PathTemplate {
+        segments: strided_segments,
+        expansion_count,
+    })
+}
+
+fn parse_placeholder(
+    placeholder: &str,
+    wordlists: &HashMap<String, Vec<String>>,
+) -> eyre::Result<TemplateSegment> {
+    if let Some((first, last)) = placeholder.split_once("..") {
+        let (Ok(start), Ok(end)) = (first.parse::<u64>(), last.parse::<u64>()) else {
+            return Err(eyre!("invalid range: {{{}}}", placeholder));
+        };
+        if start > end {
+            return Err(eyre!("empty range: {{{}}}", placeholder));
+        }
+        let width = match first.starts_with('0') && first.len() > 1 {
+            true => first.len(),
+            false => 0,
+        };
+
+        let len = (end - start)
+            .checked_add(1)
+            .ok_or_else(|| eyre!("range is too large: {{{}}}", placeholder))?;
+
+        return Ok(TemplateSegment::Range { start, len, width });
+    }
+
+    if placeholder.contains(',') {
+        return Ok(TemplateSegment::Choices(
+            placeholder.split(',').map(normalize_chunk_path).collect(),
+        ));
+    }
+
+    wordlists
+        .get(placeholder)
+        .cloned()
+        .map(TemplateSegment::Choices)
+        .ok_or_else(|| eyre!("no wordlist for placeholder: {{{}}}", placeholder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_all(template: &PathTemplate) -> Vec<String> {
+        (0..template.expansion_count)
+            .map(|index| {
+                let mut path = String::new();
+                template.expand_into(index, &mut path);
+                path
+            })
+            .collect()
+    }
+
+    fn parse(template: &str) -> eyre::Result<PathTemplate> {
+        let wordlists = HashMap::from([(
+            "champion".to_string(),
+            vec!["aatrox".to_string(), "ahri".to_string()],
+        )]);
+        parse_template(template, &wordlists)
+    }
+
+    fn parse_error(template: &str) -> String {
+        match parse(template) {
+            Ok(_) => panic!("template should not parse: {}", template),
+            Err(error) => format!("{:#}", error),
+        }
+    }
+
+    #[test]
+    fn expands_wordlists_and_alternatives() {
+        let template = parse("DATA/{champion}.{bin,JSON}").unwrap();
+        assert_eq!(
+            expand_all(&template),
+            [
+                "data/aatrox.bin",
+                "data/aatrox.json",
+                "data/ahri.bin",
+                "data/ahri.json",
+            ]
+        );
+    }
+
+    #[test]
+    fn expands_ranges_with_zero_padding() {
+        assert_eq!(
+            expand_all(&parse("{8..11}").unwrap()),
+            ["8", "9", "10", "11"]
+        );
+        assert_eq!(
+            expand_all(&parse("skin{08..11}.bin").unwrap()),
+            ["skin08.bin", "skin09.bin", "skin10.bin", "skin11.bin"]
+        );
+        assert_eq!(expand_all(&parse("{0..1}").unwrap()), ["0", "1"]);
+        assert_eq!(expand_all(&parse("{000..1}").unwrap()), ["000", "001"]);
+    }
+
+    #[test]
+    fn strides_make_the_last_segment_change_fastest() {
+        let template = parse("{a,b}/{1..3}").unwrap();
+        let strides = template
+            .segments
+            .iter()
+            .map(|(_, stride)| *stride)
+            .collect::<Vec<_>>();
+        assert_eq!(strides, [3, 3, 1]);
+        assert_eq!(
+            expand_all(&template),
+            ["a/1", "a/2", "a/3", "b/1", "b/2", "b/3"]
+        );
+    }
+
+    #[test]
+    fn ranges_expand_lazily() {
+        let template = parse("{0..18446744073709551614}.bin").unwrap();
+        assert_eq!(template.expansion_count, u64::MAX);
+
+        let mut path = String::new();
+        template.expand_into(u64::MAX - 1, &mut path);
+        assert_eq!(path, "18446744073709551614.bin");
+    }
+
+    #[test]
+    fn rejects_oversized_templates() {
+        assert!(parse_error("{0..18446744073709551615}").contains("range is too large"));
+        assert!(parse_error("{0..4294967296}/{0..4294967296}").contains("too many paths"));
+    }
+
+    #[test]
+    fn rejects_malformed_placeholders() {
+        assert!(parse_error("data/{a,b").contains("unclosed placeholder"));
+        assert!(parse_error("data/a}.bin").contains("unmatched '}'"));
+        assert!(parse_error("{a,b}}").contains("unmatched '}'"));
+        assert!(parse_error("{a{b}}").contains("nested placeholder"));
+        assert!(parse_error("{3..1}").contains("empty range"));
+        assert!(parse_error("{a..2}").contains("invalid range"));
+        assert!(parse_error("{-1..2}").contains("invalid range"));
+        assert!(parse_error("{skin}").contains("no wordlist for placeholder"));
+    }
+}
@@ -0,0 +1,182 @@
+use camino::Utf8Path;
+use eyre::{eyre, Context};
+use league_toolkit::hash::{elf, fnv1a};
+use std::io::{self, BufRead};
+
+use crate::utils::{
+    default_hashtable_dir, format_chunk_path_hash, hash_chunk_path, normalize_chunk_path,
+    BinHashtable, WadHashtable,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum HashKind {
+    /// xxhash64 of the lowercased path with forward slashes, used for wad chunk paths
+    #[default]
+    Wad,
+    /// 32-bit FNV-1a of the lowercased name, used for property bin entries, classes and fields
+    Fnv1a,
+    /// 32-bit ELF hash of the lowercased name, used for inibin and troybin keys
+    Elf,
+}
+
+impl HashKind {
+    /// Name of the kind, as accepted by `--kind`
+    pub fn name(self) -> &'static str {
+        match self {
+            HashKind::Wad => "wad",
+            HashKind::Fnv1a => "fnv1a",
+            HashKind::Elf => "elf",
+        }
+    }
+
+    pub fn hex_digits(self) -> usize {
+        match self {
+            HashKind::Wad => 16,
+            HashKind::Fnv1a | HashKind::Elf => 8,
+        }
+    }
+
+    /// Normalizes the value the way the game does before hashing it
+    pub fn normalize(self, value: &str) -> String {
+        match self {
+            HashKind::Wad => normalize_chunk_path(value),
+            HashKind::Fnv1a | HashKind::Elf => value.to_lowercase(),
+        }
+    }
+
+    pub fn hash(self, value: &str) -> u64 {
+        match self {
+            HashKind::Wad => hash_chunk_path(value),
+            HashKind::Fnv1a => fnv1a::hash_lower(value) as u64,
+            HashKind::Elf => elf::elf(value.to_lowercase()) as u64 & 0xFFFFFFFF,
+        }
+    }
+
+    pub fn format_hash(self, hash: u64) -> String {
+        match self {
+            HashKind::Wad => format_chunk_path_hash(hash),
+            HashKind::Fnv1a | HashKind::Elf => format!("{:08x}", hash),
+        }
+    }
+}
+
+pub struct HashArgs {
+    /// Values to hash (or hashes to look up in reverse mode), read from stdin when empty
+    pub values: Vec<String>,
+    pub kind: HashKind,
+    pub reverse: bool,
+    pub hashtable: Option<String>,
+    pub hashtable_dir: Option<String>,
+    pub strict_hashtables: bool,
+}
+
+pub fn hash(args: HashArgs) -> eyre::Result<()> {
+    let values = match args.values.is_empty() {
+        true => io::stdin()
+            .lock()
+            .lines()
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("failed to read values from stdin")?
+            .into_iter()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect(),
+        false => args.values.clone(),
+    };
+
+    match args.reverse {
+        true => reverse_lookup(&args, &values),
+        false => {
+            for value in &values {
+                println!(
+                    "{} {}",
+                    args.kind.format_hash(args.kind.hash(value)),
+                    args.kind.normalize(value)
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+fn reverse_lookup(args: &HashArgs, values: &[String]) -> eyre::Result<()> {
+    let hashes = values
+        .iter()
+        .map(|value| parse_hash(value, args.kind.hex_digits()))
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    // Resolves a hash to its name, `None` if it is unknown
+    let resolve: Box<dyn Fn(u64) -> Option<String>> = match args.kind {
+        HashKind::Wad => {
+            let mut hashtable = WadHashtable::with_path_hashes(hashes.iter().copied())?;
+            hashtable.set_strict(args.strict_hashtables);
+            if let Some(dir_override) = &args.hashtable_dir {
+                hashtable.add_from_dir(Utf8Path::new(dir_override))?;
+            } else if let Some(dir) = default_hashtable_dir() {
+                hashtable.add_from_dir(dir)?;
+            }
+            if let Some(hashtable_path) = &args.hashtable {
+                hashtable.add_from_file(Utf8Path::new(hashtable_path))?;
+            }
+
+            Box::new(move |hash| {
+                hashtable
+                    .contains(hash)
+                    .then(|| hashtable.resolve_path(hash).to_string())
+            })
+        }
+        HashKind::Fnv1a => {
+            let mut bin_hashtable = BinHashtable::new()?;
+            bin_hashtable.set_strict(args.strict_hashtables);
+            if let Some(dir_override) = &args.hashtable_dir {
+                bin_hashtable.add_from_dir(Utf8Path::new(dir_override))?;
+            } else if let Some(dir) = default_hashtable_dir() {
+                bin_hashtable.add_from_dir(dir)?;
+            }
+            if let Some(hashtable_path) = &args.hashtable {
+                bin_hashtable.add_from_file(Utf8Path::new(hashtable_path))?;
+            }
+
+            Box::new(move |hash| {
+                let hash = hash as u32;
+                bin_hashtable
+                    .contains(hash)
+                    .then(|| bin_hashtable.resolve(hash).to_string())
+            })
+        }
+        HashKind::Elf => {
+            return Err(eyre!(
+                "reverse lookup is not supported for elf hashes, there are no elf hashtables"
+            ))
+        }
+    };
+
+    let mut unknown_count = 0;
+    for hash in hashes {
+        match resolve(hash) {
+            Some(name) => println!("{} {}", args.kind.format_hash(hash), name),
+            None => {
+                unknown_count += 1;
+                tracing::warn!("unknown hash: {}", args.kind.format_hash(hash));
+            }
+        }
+    }
+    if unknown_count > 0 {
+        tracing::warn!("{} of {} hashes are unknown", unknown_count, values.len());
+    }
+
+    Ok(())
+}
+
+fn parse_hash(value: &str, hex_digits: usize) -> eyre::Result<u64> {
+    let hex = value.trim_start_matches("0x");
+    if hex.is_empty() || hex.len() > hex_digits {
+        return Err(eyre!(
+            "invalid hash (expected up to {} hex digits): {}",
+            hex_digits,
+            value
+        ));
+    }
+
+    u64::from_str_radix(hex, 16).wrap_err(format!("invalid hash: {}", value))
+}
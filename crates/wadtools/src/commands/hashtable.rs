@@ -0,0 +1,681 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use colored::Colorize;
+use eyre::{eyre, Context};
+use indexmap::{map::Entry, IndexMap};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    io::{BufWriter, Write},
+};
+use walkdir::WalkDir;
+
+use super::HashKind;
+use crate::utils::{
+    default_hashtable_dir, for_each_hashtable_line, is_bin_hashtable_file,
+    is_hashtable_bookkeeping_file, is_text_file, PartialFile,
+};
+
+/// Number of conflicts reported by `merge` and `dedupe`, the rest are only counted
+const MAX_REPORTED_CONFLICTS: usize = 10;
+
+pub struct HashtableMergeArgs {
+    /// Hashtable files or directories, later entries win over earlier ones
+    pub input: Vec<String>,
+    pub output: String,
+    pub kind: Option<HashKind>,
+    /// Drop entries whose name doesn't hash to their hash
+    pub drop_invalid: bool,
+    pub strict_hashtables: bool,
+}
+
+pub struct HashtableDedupeArgs {
+    pub input: String,
+    /// Rewrites the input when not set
+    pub output: Option<String>,
+    pub kind: Option<HashKind>,
+    pub drop_invalid: bool,
+    pub strict_hashtables: bool,
+}
+
+pub struct HashtableValidateArgs {
+    /// Hashtable files or directories, the hashtable directory when empty
+    pub input: Vec<String>,
+    pub kind: Option<HashKind>,
+    pub hashtable_dir: Option<String>,
+    pub strict_hashtables: bool,
+}
+
+pub struct HashtableDiffArgs {
+    pub reference: String,
+    pub target: String,
+    pub kind: Option<HashKind>,
+    pub strict_hashtables: bool,
+}
+
+pub struct HashtableStatsArgs {
+    /// Hashtable files or directories, the hashtable directory when empty
+    pub input: Vec<String>,
+    pub kind: Option<HashKind>,
+    pub hashtable_dir: Option<String>,
+    pub strict_hashtables: bool,
+}
+
+struct HashtableEntry {
+    line: usize,
+    hash: u64,
+    name: String,
+    /// Whether the name hashes to the hash
+    valid: bool,
+}
+
+/// The entries of a text hashtable, in file order
+struct HashtableFile {
+    path: Utf8PathBuf,
+    kind: HashKind,
+    entries: Vec<HashtableEntry>,
+    malformed_count: usize,
+}
+
+impl HashtableFile {
+    /// Loads the file, the kind is detected from the file name when not given
+    fn load(path: &Utf8Path, kind: Option<HashKind>, strict: bool) -> eyre::Result<Self> {
+        let kind = kind.unwrap_or_else(|| match is_bin_hashtable_file(path) {
+            true => HashKind::Fnv1a,
+            false => HashKind::Wad,
+        });
+
+        let mut entries = Vec::new();
+        let malformed_count =
+            for_each_hashtable_line(path, kind.hex_digits(), strict, |line, hash, name| {
+                entries.push(HashtableEntry {
+                    line,
+                    hash,
+                    name: name.to_string(),
+                    valid: kind.hash(name) == hash,
+                })
+            })?;
+
+        Ok(HashtableFile {
+            path: path.to_path_buf(),
+            kind,
+            entries,
+            malformed_count,
+        })
+    }
+}
+
+enum IssueKind {
+    /// The name hashes to a different value
+    HashMismatch { actual: u64 },
+    /// The same entry appears earlier in the same file
+    Duplicate { first_line: usize },
+    /// An earlier entry maps the hash to a different name
+    Conflict { file: usize, entry: usize },
+}
+
+struct HashtableIssue {
+    file: usize,
+    entry: usize,
+    kind: IssueKind,
+}
+
+impl HashtableIssue {
+    fn describe(&self, files: &[HashtableFile]) -> String {
+        let file = &files[self.file];
+        let entry = &file.entries[self.entry];
+        let message = match &self.kind {
+            IssueKind::HashMismatch { actual } => format!(
+                "{} hashes to {}, not {}",
+                entry.name,
+                file.kind.format_hash(*actual),
+                file.kind.format_hash(entry.hash)
+            ),
+            IssueKind::Duplicate { first_line } => format!(
+                "duplicate entry for {} (first on line {})",
+                file.kind.format_hash(entry.hash),
+                first_line
+            ),
+            IssueKind::Conflict {
+                file: other_file,
+                entry: other_entry,
+            } => {
+                let other_file = &files[*other_file];
+                let other_entry = &other_file.entries[*other_entry];
+                format!(
+                    "{} maps to {}, but to {} in {}:{}",
+                    file.kind.format_hash(entry.hash),
+                    entry.name,
+                    other_entry.name,
+                    other_file.path,
+                    other_entry.line
+                )
+            }
+        };
+
+        format!("{}:{}: {}", file.path, entry.line, message)
+    }
+}
+
+/// Finds hash mismatches, duplicate entries within a file and conflicting entries across all
+/// files. Every entry is compared against the first entry of its hash; names differing only in
+/// case or slashes are not conflicts, since they hash the same.
+fn find_issues(files: &[HashtableFile]) -> Vec<HashtableIssue> {
+    let mut issues = Vec::new();
+    let mut first_entries = HashMap::<(HashKind, u64), (usize, usize)>::new();
+    for (file_index, file) in files.iter().enumerate() {
+        for (entry_index, entry) in file.entries.iter().enumerate() {
+            if !entry.valid {
+                issues.push(HashtableIssue {
+                    file: file_index,
+                    entry: entry_index,
+                    kind: IssueKind::HashMismatch {
+                        actual: file.kind.hash(&entry.name),
+                    },
+                });
+            }
+
+            let (first_file, first_entry) = *first_entries
+                .entry((file.kind, entry.hash))
+                .or_insert((file_index, entry_index));
+            if (first_file, first_entry) == (file_index, entry_index) {
+                continue;
+            }
+
+            let first = &files[first_file].entries[first_entry];
+            let kind = match file.kind.normalize(&first.name) == file.kind.normalize(&entry.name) {
+                true if first_file == file_index => IssueKind::Duplicate {
+                    first_line: first.line,
+                },
+                true => continue,
+                false => IssueKind::Conflict {
+                    file: first_file,
+                    entry: first_entry,
+                },
+            };
+            issues.push(HashtableIssue {
+                file: file_index,
+                entry: entry_index,
+                kind,
+            });
+        }
+    }
+
+    issues
+}
+
+pub fn hashtable_merge(args: HashtableMergeArgs) -> eyre::Result<()> {
+    let paths = collect_hashtable_files(&args.input, None)?;
+    let files = load_hashtable_files(&paths, args.kind, args.strict_hashtables)?;
+    let kind = single_kind(&files)?;
+
+    let mut entries = resolve_entries(&files, args.drop_invalid);
+    entries.sort_keys();
+    write_hashtable(Utf8Path::new(&args.output), kind, &entries)?;
+
+    tracing::info!(
+        "merged {} files into {} ({} entries)",
+        files.len(),
+        args.output,
+        entries.len()
+    );
+    Ok(())
+}
+
+pub fn hashtable_dedupe(args: HashtableDedupeArgs) -> eyre::Result<()> {
+    let input = Utf8Path::new(&args.input);
+    let file = HashtableFile::load(input, args.kind, args.strict_hashtables)?;
+    let kind = file.kind;
+    let files = [file];
+
+    let entries = resolve_entries(&files, args.drop_invalid);
+    let output = args.output.as_deref().map_or(input, Utf8Path::new);
+    write_hashtable(output, kind, &entries)?;
+
+    tracing::info!(
+        "wrote {} of {} entries to {}",
+        entries.len(),
+        files[0].entries.len(),
+        output
+    );
+    Ok(())
+}
+
+pub fn hashtable_validate(args: HashtableValidateArgs) -> eyre::Result<()> {
+    let paths = collect_hashtable_files(&args.input, args.hashtable_dir.as_deref())?;
+    let files = load_hashtable_files(&paths, args.kind, args.strict_hashtables)?;
+
+    let issues = find_issues(&files);
+    for issue in &issues {
+        println!("{}", issue.describe(&files));
+    }
+
+    let entry_count = files.iter().map(|file| file.entries.len()).sum::<usize>();
+    let malformed_count = files.iter().map(|file| file.malformed_count).sum::<usize>();
+    let count = |matches: fn(&IssueKind) -> bool| {
+        issues.iter().filter(|issue| matches(&issue.kind)).count()
+    };
+    tracing::info!(
+        "checked {} entries in {} files: {} hash mismatches, {} duplicates, {} conflicts, {} malformed lines",
+        entry_count,
+        files.len(),
+        count(|kind| matches!(kind, IssueKind::HashMismatch { .. })),
+        count(|kind| matches!(kind, IssueKind::Duplicate { .. })),
+        count(|kind| matches!(kind, IssueKind::Conflict { .. })),
+        malformed_count
+    );
+
+    match issues.len() + malformed_count {
+        0 => Ok(()),
+        problem_count => Err(eyre!("found {} problems in hashtables", problem_count)),
+    }
+}
+
+pub fn hashtable_diff(args: HashtableDiffArgs) -> eyre::Result<()> {
+    let reference = HashtableFile::load(
+        Utf8Path::new(&args.reference),
+        args.kind,
+        args.strict_hashtables,
+    )?;
+    let target = HashtableFile::load(
+        Utf8Path::new(&args.target),
+        Some(reference.kind),
+        args.strict_hashtables,
+    )?;
+    let kind = reference.kind;
+
+    // Later entries win, like when loading
+    let reference = reference
+        .entries
+        .iter()
+        .map(|entry| (entry.hash, entry.name.as_str()))
+        .collect::<BTreeMap<_, _>>();
+    let target = target
+        .entries
+        .iter()
+        .map(|entry| (entry.hash, entry.name.as_str()))
+        .collect::<BTreeMap<_, _>>();
+
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    let hashes = reference
+        .keys()
+        .chain(target.keys())
+        .copied()
+        .collect::<BTreeSet<_>>();
+    for hash in hashes {
+        match (reference.get(&hash), target.get(&hash)) {
+            (None, Some(name)) => {
+                added += 1;
+                println!("+ {} {}", kind.format_hash(hash), name.bright_green());
+            }
+            (Some(name), None) => {
+                removed += 1;
+                println!("- {} {}", kind.format_hash(hash), name.bright_red());
+            }
+            (Some(old_name), Some(new_name)) if old_name != new_name => {
+                changed += 1;
+                println!(
+                    "~ {} {} -> {}",
+                    kind.format_hash(hash),
+                    old_name.bright_red(),
+                    new_name.bright_green()
+                );
+            }
+            _ => {}
+        }
+    }
+
+    tracing::info!("{} added, {} removed, {} changed", added, removed, changed);
+    Ok(())
+}
+
+pub fn hashtable_stats(args: HashtableStatsArgs) -> eyre::Result<()> {
+    let paths = collect_hashtable_files(&args.input, args.hashtable_dir.as_deref())?;
+    let files = load_hashtable_files(&paths, args.kind, args.strict_hashtables)?;
+    let issues = find_issues(&files);
+
+    println!(
+        "{:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:6}  {}",
+        "ENTRIES".bright_blue().bold(),
+        "UNIQUE".bright_blue().bold(),
+        "DUPLICATE".bright_blue().bold(),
+        "CONFLICT".bright_blue().bold(),
+        "MISMATCH".bright_blue().bold(),
+        "MALFORMED".bright_blue().bold(),
+        "KIND".bright_blue().bold(),
+        "FILE".bright_blue().bold()
+    );
+    println!("{}", "─".repeat(80).bright_black());
+
+    let mut totals = [0usize; 6];
+    for (file_index, file) in files.iter().enumerate() {
+        let count = |matches: fn(&IssueKind) -> bool| {
+            issues
+                .iter()
+                .filter(|issue| issue.file == file_index && matches(&issue.kind))
+                .count()
+        };
+        let row = [
+            file.entries.len(),
+            file.entries
+                .iter()
+                .map(|entry| entry.hash)
+                .collect::<HashSet<_>>()
+                .len(),
+            count(|kind| matches!(kind, IssueKind::Duplicate { .. })),
+            count(|kind| matches!(kind, IssueKind::Conflict { .. })),
+            count(|kind| matches!(kind, IssueKind::HashMismatch { .. })),
+            file.malformed_count,
+        ];
+        for (total, value) in totals.iter_mut().zip(row) {
+            *total += value;
+        }
+
+        println!(
+            "{:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:6}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            row[4],
+            row[5],
+            file.kind.name().bright_magenta(),
+            file.path
+        );
+    }
+
+    // Unique across files, so not the sum of the rows
+    totals[1] = files
+        .iter()
+        .flat_map(|file| file.entries.iter().map(|entry| (file.kind, entry.hash)))
+        .collect::<HashSet<_>>()
+        .len();
+    println!("{}", "─".repeat(80).bright_black());
+    println!(
+        "{:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:6}  {}",
+        totals[0].to_string().bright_green().bold(),
+        totals[1].to_string().bright_green().bold(),
+        totals[2],
+        totals[3],
+        totals[4],
+        totals[5],
+        "",
+        format!("{} files", files.len()).bright_white()
+    );
+
+    Ok(())
+}
+
+/// Expands directories into the text hashtables they contain, in path order. Falls back to the
+/// hashtable directory when no input is given.
+fn collect_hashtable_files(
+    input: &[String],
+    hashtable_dir: Option<&str>,
+) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let input = match input.is_empty() {
+        true => vec![match hashtable_dir {
+            Some(dir) => Utf8PathBuf::from(dir),
+            None => default_hashtable_dir()
+                .ok_or_else(|| eyre!("no input given and no default hashtable directory"))?,
+        }],
+        false => input.iter().map(Utf8PathBuf::from).collect(),
+    };
+
+    let mut paths = Vec::new();
+    for path in input {
+        if path.is_file() {
+            paths.push(path);
+            continue;
+        }
+        if !path.is_dir() {
+            return Err(eyre!("hashtable not found: {}", path));
+        }
+
+        for entry in WalkDir::new(path.as_std_path())
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|x| x.ok())
+        {
+            if !entry.file_type().is_file() || is_hashtable_bookkeeping_file(entry.path()) {
+                continue;
+            }
+            let Some(path) = Utf8Path::from_path(entry.path()) else {
+                tracing::warn!("skipping hashtable with non UTF-8 path: {:?}", entry.path());
+                continue;
+            };
+            if !is_text_file(path)? {
+                tracing::debug!("skipping non-text file in hashtable directory: {}", path);
+                continue;
+            }
+            paths.push(path.to_path_buf());
+        }
+    }
+
+    if paths.is_empty() {
+        return Err(eyre!("no hashtable files found"));
+    }
+    Ok(paths)
+}
+
+fn load_hashtable_files(
+    paths: &[Utf8PathBuf],
+    kind: Option<HashKind>,
+    strict: bool,
+) -> eyre::Result<Vec<HashtableFile>> {
+    paths
+        .iter()
+        .map(|path| {
+            tracing::info!("loading hashtable: {}", path);
+            HashtableFile::load(path, kind, strict)
+        })
+        .collect()
+}
+
+/// Files of different kinds can't be written into a single hashtable
+fn single_kind(files: &[HashtableFile]) -> eyre::Result<HashKind> {
+    let kind = files[0].kind;
+    if let Some(other) = files.iter().find(|file| file.kind != kind) {
+        return Err(eyre!(
+            "{} and {} hold different kinds of hashes ({} and {}), pass --kind to override",
+            files[0].path,
+            other.path,
+            kind.name(),
+            other.kind.name()
+        ));
+    }
+
+    Ok(kind)
+}
+
+/// Keeps one entry per hash, in order of first appearance. Later entries win like when loading,
+/// except that an entry whose name hashes correctly is never replaced by one that doesn't.
+fn resolve_entries(files: &[HashtableFile], drop_invalid: bool) -> IndexMap<u64, &str> {
+    let mut resolved = IndexMap::<u64, &HashtableEntry>::new();
+    let (mut invalid_count, mut duplicate_count) = (0, 0);
+    for entry in files.iter().flat_map(|file| &file.entries) {
+        if drop_invalid && !entry.valid {
+            invalid_count += 1;
+            continue;
+        }
+
+        match resolved.entry(entry.hash) {
+            Entry::Vacant(vacant) => {
+                vacant.insert(entry);
+            }
+            Entry::Occupied(mut occupied) => {
+                duplicate_count += 1;
+                if entry.valid || !occupied.get().valid {
+                    occupied.insert(entry);
+                }
+            }
+        }
+    }
+
+    let conflicts = find_issues(files)
+        .into_iter()
+        .filter(|issue| matches!(issue.kind, IssueKind::Conflict { .. }))
+        .collect::<Vec<_>>();
+    for conflict in conflicts.iter().take(MAX_REPORTED_CONFLICTS) {
+        tracing::warn!("conflicting entry: {}", conflict.describe(files));
+    }
+    if conflicts.len() > MAX_REPORTED_CONFLICTS {
+        tracing::warn!(
+            "{} conflicting entries in total (see `hashtable validate`)",
+            conflicts.len()
+        );
+    }
+    tracing::info!(
+        "dropped {} entries of already seen hashes and {} invalid entries",
+        duplicate_count,
+        invalid_count
+    );
+
+    resolved
+        .into_iter()
+        .map(|(hash, entry)| (hash, entry.name.as_str()))
+        .collect()
+}
+
+/// Writes the entries through a partial file, so the output is never left half written even
+/// when it is one of the inputs
+fn write_hashtable(
+    path: &Utf8Path,
+    kind: HashKind,
+    entries: &IndexMap<u64, &str>,
+) -> eyre::Result<()> {
+    let mut output_file = PartialFile::create(path)?;
+    let mut writer = BufWriter::new(output_file.file());
+    for (hash, name) in entries {
+        writeln!(writer, "{} {}", kind.format_hash(*hash), name)?;
+    }
+    writer
+        .flush()
+        .wrap_err(format!("failed to write hashtable: {}", path))?;
+    drop(writer);
+
+    output_file.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A wad hashtable of `(hash, name)` entries, the hash is computed from the name when `None`
+    fn hashtable_file(path: &str, entries: &[(Option<&str>, &str)]) -> HashtableFile {
+        let kind = HashKind::Wad;
+        let entries = entries
+            .iter()
+            .enumerate()
+            .map(|(index, (hashed_name, name))| {
+                let hash = kind.hash(hashed_name.unwrap_or(name));
+                HashtableEntry {
+                    line: index + 1,
+                    hash,
+                    name: name.to_string(),
+                    valid: kind.hash(name) == hash,
+                }
+            })
+            .collect();
+
+        HashtableFile {
+            path: Utf8PathBuf::from(path),
+            kind,
+            entries,
+            malformed_count: 0,
+        }
+    }
+
+    fn describe_issues(files: &[HashtableFile]) -> Vec<String> {
+        find_issues(files)
+            .iter()
+            .map(|issue| {
+                let kind = match issue.kind {
+                    IssueKind::HashMismatch { .. } => "mismatch".to_string(),
+                    IssueKind::Duplicate { first_line } => format!("duplicate of {}", first_line),
+                    IssueKind::Conflict { file, entry } => {
+                        format!("conflict with {}:{}", file, entry)
+                    }
+                };
+                format!("{}:{} {}", issue.file, issue.entry, kind)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn find_issues_classifies_duplicates_and_conflicts() {
+        let files = [
+            hashtable_file(
+                "a.txt",
+                &[
+                    (None, "data/a.bin"),
+                    (None, "data/b.bin"),
+                    // Hashes the same, so a duplicate rather than a conflict
+                    (None, "DATA\\A.bin"),
+                    (Some("data/b.bin"), "data/wrong.bin"),
+                ],
+            ),
+            hashtable_file(
+                "b.txt",
+                &[
+                    // The same entry in another file is fine
+                    (None, "data/a.bin"),
+                    (Some("data/a.bin"), "data/other.bin"),
+                    (None, "data/c.bin"),
+                ],
+            ),
+        ];
+
+        assert_eq!(
+            describe_issues(&files),
+            [
+                "0:2 duplicate of 1",
+                "0:3 mismatch",
+                "0:3 conflict with 0:1",
+                "1:1 mismatch",
+                "1:1 conflict with 0:0",
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_entries_never_replaces_valid_entries_with_invalid_ones() {
+        let files = [
+            hashtable_file(
+                "a.txt",
+                &[
+                    (None, "data/a.bin"),
+                    (Some("data/b.bin"), "data/wrong_b.bin"),
+                    (Some("data/c.bin"), "data/wrong_c.bin"),
+                ],
+            ),
+            hashtable_file(
+                "b.txt",
+                &[
+                    (Some("data/a.bin"), "data/wrong_a.bin"),
+                    (None, "data/b.bin"),
+                    // Later valid entries win
+                    (None, "DATA/A.bin"),
+                ],
+            ),
+        ];
+
+        let kind = HashKind::Wad;
+        let resolved = resolve_entries(&files, false);
+        assert_eq!(
+            resolved.into_iter().collect::<Vec<_>>(),
+            [
+                (kind.hash("data/a.bin"), "DATA/A.bin"),
+                (kind.hash("data/b.bin"), "data/b.bin"),
+                (kind.hash("data/c.bin"), "data/wrong_c.bin"),
+            ]
+        );
+
+        let resolved = resolve_entries(&files, true);
+        assert_eq!(
+            resolved.into_iter().collect::<Vec<_>>(),
+            [
+                (kind.hash("data/a.bin"), "DATA/A.bin"),
+                (kind.hash("data/b.bin"), "data/b.bin"),
+            ]
+        );
+    }
+}
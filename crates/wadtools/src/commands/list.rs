@@ -1,9 +1,13 @@
 use camino::Utf8Path;
 use color_eyre::owo_colors::OwoColorize;
-use league_toolkit::{file::LeagueFileKind, wad::Wad};
+use league_toolkit::{
+    file::LeagueFileKind,
+    wad::{Wad, WadChunkCompression},
+};
 use serde::Serialize;
-use std::fs::File;
+use std::{cmp::Ordering, collections::BTreeMap, fs::File};
 
+use crate::chunk_kinds::ChunkKindCache;
 use crate::utils::{
     create_filter_pattern, default_hashtable_dir, format_chunk_path_hash, WadHashtable,
 };
@@ -19,15 +23,203 @@ pub enum ListOutputFormat {
     Csv,
     /// Simple flat list of paths only
     Flat,
+    /// Directory tree with per-directory chunk counts and sizes
+    Tree,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ListSortKey {
+    /// Resolved path
+    #[default]
+    Path,
+    /// Uncompressed size
+    Size,
+    /// Compressed size
+    Compressed,
+    /// Compression ratio
+    Ratio,
+    /// File type
+    Type,
+    /// Path hash
+    Hash,
+}
+
+/// Chunk compression to filter by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ListCompression {
+    None,
+    Gzip,
+    Satellite,
+    Zstd,
+    /// Uncompressed data followed by zstd frames
+    ZstdMulti,
+}
+
+impl ListCompression {
+    fn matches(self, compression: WadChunkCompression) -> bool {
+        let expected = match self {
+            ListCompression::None => WadChunkCompression::None,
+            ListCompression::Gzip => WadChunkCompression::GZip,
+            ListCompression::Satellite => WadChunkCompression::Satellite,
+            ListCompression::Zstd => WadChunkCompression::Zstd,
+            ListCompression::ZstdMulti => WadChunkCompression::ZstdMulti,
+        };
+        compression == expected
+    }
+}
+
+/// A `ChunkInfo` field shown by the table and csv formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ListColumn {
+    Hash,
+    Path,
+    Type,
+    Compressed,
+    Size,
+    Ratio,
+    Compression,
+    Checksum,
+    Offset,
+    Duplicate,
+    SubchunkStart,
+    SubchunkCount,
+}
+
+impl ListColumn {
+    const TABLE_DEFAULT: [ListColumn; 6] = [
+        ListColumn::Compressed,
+        ListColumn::Size,
+        ListColumn::Ratio,
+        ListColumn::Compression,
+        ListColumn::Type,
+        ListColumn::Path,
+    ];
+
+    const CSV_DEFAULT: [ListColumn; 12] = [
+        ListColumn::Hash,
+        ListColumn::Path,
+        ListColumn::Type,
+        ListColumn::Compressed,
+        ListColumn::Size,
+        ListColumn::Ratio,
+        ListColumn::Compression,
+        ListColumn::Checksum,
+        ListColumn::Offset,
+        ListColumn::Duplicate,
+        ListColumn::SubchunkStart,
+        ListColumn::SubchunkCount,
+    ];
+
+    /// Header of the csv format, the name of the `ChunkInfo` field
+    fn field_name(self) -> &'static str {
+        match self {
+            ListColumn::Hash => "hash",
+            ListColumn::Path => "path",
+            ListColumn::Type => "file_type",
+            ListColumn::Compressed => "compressed_size",
+            ListColumn::Size => "uncompressed_size",
+            ListColumn::Ratio => "compression_ratio",
+            ListColumn::Compression => "compression",
+            ListColumn::Checksum => "checksum",
+            ListColumn::Offset => "data_offset",
+            ListColumn::Duplicate => "duplicate",
+            ListColumn::SubchunkStart => "subchunk_start",
+            ListColumn::SubchunkCount => "subchunk_count",
+        }
+    }
+
+    fn table_header(self) -> &'static str {
+        match self {
+            ListColumn::Hash => "HASH",
+            ListColumn::Path => "PATH",
+            ListColumn::Type => "TYPE",
+            ListColumn::Compressed => "COMPRESSED",
+            ListColumn::Size => "SIZE",
+            ListColumn::Ratio => "RATIO",
+            ListColumn::Compression => "COMPRESSION",
+            ListColumn::Checksum => "CHECKSUM",
+            ListColumn::Offset => "OFFSET",
+            ListColumn::Duplicate => "DUPLICATE",
+            ListColumn::SubchunkStart => "SUB START",
+            ListColumn::SubchunkCount => "SUB COUNT",
+        }
+    }
+
+    /// Width of the column in the table format, `None` for the unpadded path
+    fn table_width(self) -> Option<usize> {
+        match self {
+            ListColumn::Hash | ListColumn::Checksum | ListColumn::Type => Some(16),
+            ListColumn::Path => None,
+            ListColumn::Compressed | ListColumn::Size | ListColumn::Offset => Some(12),
+            ListColumn::Ratio => Some(7),
+            ListColumn::Compression => Some(11),
+            ListColumn::Duplicate => Some(9),
+            ListColumn::SubchunkStart | ListColumn::SubchunkCount => Some(9),
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            ListColumn::Compressed
+                | ListColumn::Size
+                | ListColumn::Ratio
+                | ListColumn::Offset
+                | ListColumn::SubchunkStart
+                | ListColumn::SubchunkCount
+        )
+    }
+
+    fn csv_value(self, chunk: &ChunkInfo) -> String {
+        match self {
+            ListColumn::Hash => chunk.hash.clone(),
+            ListColumn::Path => chunk.path.clone(),
+            ListColumn::Type => chunk.file_type.clone(),
+            ListColumn::Compressed => chunk.compressed_size.to_string(),
+            ListColumn::Size => chunk.uncompressed_size.to_string(),
+            ListColumn::Ratio => chunk.compression_ratio.to_string(),
+            ListColumn::Compression => chunk.compression.clone(),
+            ListColumn::Checksum => chunk.checksum.clone(),
+            ListColumn::Offset => chunk.data_offset.to_string(),
+            ListColumn::Duplicate => chunk.duplicate.to_string(),
+            ListColumn::SubchunkStart => chunk.subchunk_start.to_string(),
+            ListColumn::SubchunkCount => chunk.subchunk_count.to_string(),
+        }
+    }
+
+    fn table_value(self, chunk: &ChunkInfo) -> String {
+        match self {
+            ListColumn::Compressed => format_size(chunk.compressed_size as u64),
+            ListColumn::Size => format_size(chunk.uncompressed_size as u64),
+            ListColumn::Ratio => format!("{:.1}%", chunk.compression_ratio),
+            ListColumn::Duplicate => if chunk.duplicate { "yes" } else { "no" }.to_string(),
+            _ => self.csv_value(chunk),
+        }
+    }
 }
 
 pub struct ListArgs {
     pub input: String,
     pub hashtable: Option<String>,
     pub hashtable_dir: Option<String>,
+    pub strict_hashtables: bool,
     pub filter_type: Option<Vec<LeagueFileKind>>,
     pub pattern: Option<String>,
     pub format: ListOutputFormat,
+    /// Only print the directories of the tree format down to this depth
+    pub depth: Option<usize>,
+    /// Detect file types from the chunk contents instead of the path extension
+    pub detect: bool,
+    /// Only list chunks with one of these compressions
+    pub compression: Option<Vec<ListCompression>>,
+    /// Only list chunks flagged as duplicates of another chunk's data
+    pub duplicates_only: bool,
+    pub sort: ListSortKey,
+    pub reverse: bool,
+    /// Only keep the first chunks after sorting
+    pub limit: Option<usize>,
+    /// Columns of the table and csv formats, their defaults when `None`
+    pub columns: Option<Vec<ListColumn>>,
     pub show_stats: bool,
 }
 
@@ -39,6 +231,27 @@ struct ChunkInfo {
     compressed_size: usize,
     uncompressed_size: usize,
     compression_ratio: f64,
+    compression: String,
+    checksum: String,
+    data_offset: usize,
+    /// Whether the chunk shares its data with another chunk
+    duplicate: bool,
+    subchunk_start: u32,
+    subchunk_count: u8,
+}
+
+impl ChunkInfo {
+    fn cmp_by(&self, other: &ChunkInfo, key: ListSortKey) -> Ordering {
+        match key {
+            ListSortKey::Path => Ordering::Equal,
+            ListSortKey::Size => self.uncompressed_size.cmp(&other.uncompressed_size),
+            ListSortKey::Compressed => self.compressed_size.cmp(&other.compressed_size),
+            ListSortKey::Ratio => self.compression_ratio.total_cmp(&other.compression_ratio),
+            ListSortKey::Type => self.file_type.cmp(&other.file_type),
+            ListSortKey::Hash => self.hash.cmp(&other.hash),
+        }
+        .then_with(|| self.path.cmp(&other.path))
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -53,11 +266,23 @@ struct ListOutput {
 }
 
 pub fn list(args: ListArgs) -> eyre::Result<()> {
+    if args.depth.is_some() && !matches!(args.format, ListOutputFormat::Tree) {
+        return Err(eyre::eyre!("--depth only applies to --format tree"));
+    }
+    if args.columns.is_some()
+        && !matches!(args.format, ListOutputFormat::Table | ListOutputFormat::Csv)
+    {
+        return Err(eyre::eyre!(
+            "--columns only applies to --format table and --format csv"
+        ));
+    }
+
     let source = File::open(&args.input)?;
     let wad = Wad::mount(&source)?;
 
     // Load hashtables
-    let mut hashtable = WadHashtable::new()?;
+    let mut hashtable = WadHashtable::with_path_hashes(wad.chunks().keys().copied())?;
+    hashtable.set_strict(args.strict_hashtables);
     if let Some(dir_override) = &args.hashtable_dir {
         hashtable.add_from_dir(Utf8Path::new(dir_override))?;
     } else if let Some(dir) = default_hashtable_dir() {
@@ -65,18 +290,36 @@ pub fn list(args: ListArgs) -> eyre::Result<()> {
     }
     if let Some(hashtable_path) = &args.hashtable {
         tracing::info!("loading hashtable from {}", hashtable_path);
-        hashtable.add_from_file(&File::open(hashtable_path)?)?;
+        hashtable.add_from_file(Utf8Path::new(hashtable_path))?;
     }
 
     // Build filter pattern
     let filter_pattern = create_filter_pattern(args.pattern)?;
 
+    let mut kind_cache = args
+        .detect
+        .then(|| ChunkKindCache::load(Utf8Path::new(&args.input)));
+    let mut reader = &source;
+
     // Collect chunk info
     let mut chunks_info: Vec<ChunkInfo> = Vec::new();
     let mut total_compressed: u64 = 0;
     let mut total_uncompressed: u64 = 0;
 
     for chunk in wad.chunks().values() {
+        // Apply chunk metadata filters
+        if let Some(ref compressions) = args.compression {
+            if !compressions
+                .iter()
+                .any(|compression| compression.matches(chunk.compression_type))
+            {
+                continue;
+            }
+        }
+        if args.duplicates_only && !chunk.is_duplicated {
+            continue;
+        }
+
         let path_str = hashtable.resolve_path(chunk.path_hash);
 
         // Apply pattern filter
@@ -86,10 +329,18 @@ pub fn list(args: ListArgs) -> eyre::Result<()> {
             }
         }
 
-        // Detect file type from extension or guess from path
-        let file_type = LeagueFileKind::from_extension(
-            Utf8Path::new(path_str.as_ref()).extension().unwrap_or(""),
-        );
+        // Detect file type from the chunk contents, or guess it from the path extension
+        let file_type = match &mut kind_cache {
+            Some(kind_cache) => kind_cache
+                .identify(&mut reader, chunk)
+                .unwrap_or_else(|error| {
+                    tracing::warn!("{:#}", error);
+                    LeagueFileKind::Unknown
+                }),
+            None => LeagueFileKind::from_extension(
+                Utf8Path::new(path_str.as_ref()).extension().unwrap_or(""),
+            ),
+        };
 
         // Apply type filter
         if let Some(ref filter_types) = args.filter_type {
@@ -116,11 +367,26 @@ pub fn list(args: ListArgs) -> eyre::Result<()> {
             compressed_size: compressed,
             uncompressed_size: uncompressed,
             compression_ratio: ratio,
+            compression: chunk.compression_type.to_string(),
+            checksum: format_chunk_path_hash(chunk.checksum),
+            data_offset: chunk.data_offset,
+            duplicate: chunk.is_duplicated,
+            subchunk_start: chunk.start_frame,
+            subchunk_count: chunk.frame_count,
         });
     }
 
-    // Sort by path for consistent output
-    chunks_info.sort_by(|a, b| a.path.cmp(&b.path));
+    if let Some(kind_cache) = &mut kind_cache {
+        if let Err(error) = kind_cache.save(wad.chunks()) {
+            tracing::debug!("chunk kind cache not saved ({:#})", error);
+        }
+    }
+
+    // Ties are sorted by path for consistent output
+    chunks_info.sort_by(|a, b| a.cmp_by(b, args.sort));
+    if args.reverse {
+        chunks_info.reverse();
+    }
 
     let overall_ratio = if total_uncompressed > 0 {
         (1.0 - (total_compressed as f64 / total_uncompressed as f64)) * 100.0
@@ -128,10 +394,16 @@ pub fn list(args: ListArgs) -> eyre::Result<()> {
         0.0
     };
 
+    // Totals still cover every matched chunk
+    let matched_chunks = chunks_info.len();
+    if let Some(limit) = args.limit {
+        chunks_info.truncate(limit);
+    }
+
     let output = ListOutput {
         wad_path: args.input.clone(),
         total_chunks: wad.chunks().len(),
-        matched_chunks: chunks_info.len(),
+        matched_chunks,
         total_compressed_size: total_compressed,
         total_uncompressed_size: total_uncompressed,
         overall_compression_ratio: overall_ratio,
@@ -140,9 +412,19 @@ pub fn list(args: ListArgs) -> eyre::Result<()> {
 
     match args.format {
         ListOutputFormat::Json => print_json(&output)?,
-        ListOutputFormat::Csv => print_csv(&output)?,
+        ListOutputFormat::Csv => print_csv(
+            &output,
+            args.columns.as_deref().unwrap_or(&ListColumn::CSV_DEFAULT),
+        )?,
         ListOutputFormat::Flat => print_flat(&output),
-        ListOutputFormat::Table => print_table(&output, args.show_stats),
+        ListOutputFormat::Table => print_table(
+            &output,
+            args.columns
+                .as_deref()
+                .unwrap_or(&ListColumn::TABLE_DEFAULT),
+            args.show_stats,
+        ),
+        ListOutputFormat::Tree => print_tree(&output, args.depth, args.show_stats),
     }
 
     Ok(())
@@ -153,11 +435,12 @@ fn print_json(output: &ListOutput) -> eyre::Result<()> {
     Ok(())
 }
 
-fn print_csv(output: &ListOutput) -> eyre::Result<()> {
+fn print_csv(output: &ListOutput, columns: &[ListColumn]) -> eyre::Result<()> {
     let mut writer = csv::Writer::from_writer(std::io::stdout());
 
+    writer.write_record(columns.iter().map(|column| column.field_name()))?;
     for chunk in &output.chunks {
-        writer.serialize(chunk)?;
+        writer.write_record(columns.iter().map(|column| column.csv_value(chunk)))?;
     }
 
     writer.flush()?;
@@ -170,67 +453,230 @@ fn print_flat(output: &ListOutput) {
     }
 }
 
-fn print_table(output: &ListOutput, show_stats: bool) {
-    // Print header
-    if show_stats {
+fn print_summary(output: &ListOutput) {
+    println!(
+        "{}: {}",
+        "WAD".bright_cyan().bold(),
+        output.wad_path.bright_white()
+    );
+    if output.chunks.len() < output.matched_chunks {
         println!(
-            "{}: {}",
-            "WAD".bright_cyan().bold(),
-            output.wad_path.bright_white()
+            "{}: {} ({} matched, {} shown)",
+            "Chunks".bright_cyan().bold(),
+            output.total_chunks.to_string().bright_yellow(),
+            output.matched_chunks.to_string().bright_green(),
+            output.chunks.len().to_string().bright_white()
         );
+    } else {
         println!(
             "{}: {} ({} matched)",
             "Chunks".bright_cyan().bold(),
             output.total_chunks.to_string().bright_yellow(),
             output.matched_chunks.to_string().bright_green()
         );
+    }
+    println!(
+        "{}: {} → {} ({:.1}% saved)",
+        "Size".bright_cyan().bold(),
+        format_size(output.total_uncompressed_size).bright_white(),
+        format_size(output.total_compressed_size).bright_green(),
+        output.overall_compression_ratio
+    );
+    println!();
+}
+
+fn print_table(output: &ListOutput, columns: &[ListColumn], show_stats: bool) {
+    // Print header
+    if show_stats {
+        print_summary(output);
+    }
+
+    // Column headers
+    let headers = columns
+        .iter()
+        .map(|column| {
+            pad_table_cell(*column, column.table_header())
+                .bright_blue()
+                .bold()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    println!("{}", join_table_cells(columns, &headers));
+    println!("{}", "─".repeat(80).bright_black());
+
+    for chunk in &output.chunks {
+        let cells = columns
+            .iter()
+            .map(|column| {
+                // Pad the plain value first, then apply color
+                let cell = pad_table_cell(*column, &column.table_value(chunk));
+                match column {
+                    ListColumn::Compressed => cell.bright_black().to_string(),
+                    ListColumn::Ratio if chunk.compression_ratio > 50.0 => {
+                        cell.bright_green().to_string()
+                    }
+                    ListColumn::Ratio if chunk.compression_ratio > 20.0 => {
+                        cell.bright_yellow().to_string()
+                    }
+                    ListColumn::Ratio => cell.bright_red().to_string(),
+                    ListColumn::Type => cell.bright_magenta().to_string(),
+                    ListColumn::Hash | ListColumn::Checksum => cell.bright_black().to_string(),
+                    ListColumn::Duplicate if chunk.duplicate => cell.bright_yellow().to_string(),
+                    _ => cell,
+                }
+            })
+            .collect::<Vec<_>>();
+        println!("{}", join_table_cells(columns, &cells));
+    }
+
+    if show_stats {
+        println!("{}", "─".repeat(80).bright_black());
         println!(
-            "{}: {} → {} ({:.1}% saved)",
-            "Size".bright_cyan().bold(),
-            format_size(output.total_uncompressed_size).bright_white(),
-            format_size(output.total_compressed_size).bright_green(),
-            output.overall_compression_ratio
+            "{} chunks listed",
+            output.chunks.len().to_string().bright_green().bold()
         );
-        println!();
+    }
+}
+
+fn pad_table_cell(column: ListColumn, value: &str) -> String {
+    match column.table_width() {
+        Some(width) if column.is_numeric() => format!("{:>width$}", value),
+        Some(width) => format!("{:width$}", value),
+        None => value.to_string(),
+    }
+}
+
+/// Joins the cells of a row, leaving some more room before the path
+fn join_table_cells(columns: &[ListColumn], cells: &[String]) -> String {
+    let mut row = String::new();
+    for (index, (column, cell)) in columns.iter().zip(cells).enumerate() {
+        if index > 0 {
+            row.push_str(if *column == ListColumn::Path {
+                "  "
+            } else {
+                " "
+            });
+        }
+        row.push_str(cell);
+    }
+    row
+}
+
+/// A directory or file of the tree format, with the totals of all chunks below it
+#[derive(Default)]
+struct TreeNode<'a> {
+    children: BTreeMap<&'a str, TreeNode<'a>>,
+    /// The chunk at this path, a path can be both a chunk and a directory of other chunks
+    file: Option<&'a ChunkInfo>,
+    /// Totals of the chunks below this path
+    chunk_count: usize,
+    compressed_size: u64,
+    uncompressed_size: u64,
+}
+
+impl<'a> TreeNode<'a> {
+    fn build(chunks: &'a [ChunkInfo]) -> Self {
+        let mut root = TreeNode::default();
+        for chunk in chunks {
+            root.add(chunk);
+            let mut node = &mut root;
+            let mut components = chunk.path.split('/').peekable();
+            while let Some(component) = components.next() {
+                node = node.children.entry(component).or_default();
+                match components.peek() {
+                    Some(_) => node.add(chunk),
+                    None => node.file = Some(chunk),
+                }
+            }
+        }
+
+        root
+    }
+
+    fn add(&mut self, chunk: &ChunkInfo) {
+        self.chunk_count += 1;
+        self.compressed_size += chunk.compressed_size as u64;
+        self.uncompressed_size += chunk.uncompressed_size as u64;
+    }
+
+    fn is_dir(&self) -> bool {
+        !self.children.is_empty()
+    }
+}
+
+fn print_tree(output: &ListOutput, depth: Option<usize>, show_stats: bool) {
+    if show_stats {
+        print_summary(output);
     }
 
-    // Column headers
     println!(
-        "{:>12} {:>12} {:>7} {:16}  {}",
+        "{:>12} {:>12} {:>7}  {}",
         "COMPRESSED".bright_blue().bold(),
         "SIZE".bright_blue().bold(),
-        "RATIO".bright_blue().bold(),
-        "TYPE".bright_blue().bold(),
+        "CHUNKS".bright_blue().bold(),
         "PATH".bright_blue().bold()
     );
     println!("{}", "─".repeat(80).bright_black());
 
-    for chunk in &output.chunks {
-        // Format the ratio as a fixed-width string first, then apply color
-        let ratio_str = format!("{:>6.1}%", chunk.compression_ratio);
-        let ratio_colored = if chunk.compression_ratio > 50.0 {
-            ratio_str.bright_green().to_string()
-        } else if chunk.compression_ratio > 20.0 {
-            ratio_str.bright_yellow().to_string()
-        } else {
-            ratio_str.bright_red().to_string()
-        };
+    let root = TreeNode::build(&output.chunks);
+    println!(
+        "{:>12} {:>12} {:>7}  {}",
+        format_size(root.compressed_size).bright_black(),
+        format_size(root.uncompressed_size),
+        root.chunk_count.to_string().bright_yellow(),
+        ".".bright_cyan().bold()
+    );
+    print_tree_children(&root, "", 1, depth);
+}
 
-        println!(
-            "{:>12} {:>12} {} {:16}  {}",
-            format_size(chunk.compressed_size as u64).bright_black(),
-            format_size(chunk.uncompressed_size as u64),
-            ratio_colored,
-            chunk.file_type.bright_magenta(),
-            chunk.path
-        );
+/// Prints directories before files, both sorted by name. A path that is both a chunk and a
+/// directory gets a row for each. Past `max_depth` nothing is printed, and with a `max_depth`
+/// only directories are, like `du`.
+fn print_tree_children(node: &TreeNode, prefix: &str, depth: usize, max_depth: Option<usize>) {
+    if max_depth.is_some_and(|max_depth| depth > max_depth) {
+        return;
     }
 
-    if show_stats {
-        println!("{}", "─".repeat(80).bright_black());
+    let directories = node
+        .children
+        .iter()
+        .filter(|(_, child)| child.is_dir())
+        .map(|(name, child)| (*name, child, None));
+    let files = node
+        .children
+        .iter()
+        .filter(|_| max_depth.is_none())
+        .filter_map(|(name, child)| child.file.map(|file| (*name, child, Some(file))));
+    let rows = directories.chain(files).collect::<Vec<_>>();
+
+    for (index, (name, child, file)) in rows.iter().enumerate() {
+        let is_last = index + 1 == rows.len();
+        let connector = if is_last { "└── " } else { "├── " };
+
+        let Some(file) = file else {
+            println!(
+                "{:>12} {:>12} {:>7}  {}{}{}",
+                format_size(child.compressed_size).bright_black(),
+                format_size(child.uncompressed_size),
+                child.chunk_count.to_string().bright_yellow(),
+                prefix.bright_black(),
+                connector.bright_black(),
+                format!("{}/", name).bright_cyan().bold()
+            );
+
+            let child_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            print_tree_children(child, &child_prefix, depth + 1, max_depth);
+            continue;
+        };
+
         println!(
-            "{} chunks listed",
-            output.matched_chunks.to_string().bright_green().bold()
+            "{:>12} {:>12} {:>7}  {}{}{}",
+            format_size(file.compressed_size as u64).bright_black(),
+            format_size(file.uncompressed_size as u64),
+            "",
+            prefix.bright_black(),
+            connector.bright_black(),
+            name
         );
     }
 }
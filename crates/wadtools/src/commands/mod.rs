@@ -1,9 +1,23 @@
 mod diff;
+mod discover_hashes;
 mod download_hashes;
+mod edit;
 mod extract;
+mod guess;
+mod hash;
+mod hashtable;
 mod list;
+mod pack;
+mod unknown_hashes;
 
 pub use diff::*;
+pub use discover_hashes::*;
 pub use download_hashes::*;
+pub use edit::*;
 pub use extract::*;
+pub use guess::*;
+pub use hash::*;
+pub use hashtable::*;
 pub use list::*;
+pub use pack::*;
+pub use unknown_hashes::*;
@@ -0,0 +1,73 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use league_toolkit::wad::WadChunkCompression;
+use std::io::BufWriter;
+
+use crate::packer::{collect_pack_entries, Packer};
+use crate::utils::PartialFile;
+
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum PackCompression {
+    /// Pick the ideal compression for each chunk's file type
+    #[default]
+    Auto,
+    /// Zstandard compression
+    Zstd,
+    /// Gzip compression
+    Gzip,
+    /// Store chunks uncompressed
+    None,
+}
+
+impl PackCompression {
+    pub fn to_chunk_compression(self) -> Option<WadChunkCompression> {
+        match self {
+            PackCompression::Auto => None,
+            PackCompression::Zstd => Some(WadChunkCompression::Zstd),
+            PackCompression::Gzip => Some(WadChunkCompression::GZip),
+            PackCompression::None => Some(WadChunkCompression::None),
+        }
+    }
+}
+
+pub struct PackArgs {
+    pub input: String,
+    pub output: Option<String>,
+    pub compression: PackCompression,
+}
+
+pub fn pack(args: PackArgs) -> eyre::Result<()> {
+    let input_dir = Utf8Path::new(&args.input);
+    if !input_dir.is_dir() {
+        return Err(eyre::eyre!("Input is not a directory: {}", input_dir));
+    }
+
+    let output_path: Utf8PathBuf = match &args.output {
+        Some(path) => Utf8PathBuf::from(path.as_str()),
+        None => default_output_path(input_dir),
+    };
+
+    tracing::info!("collecting files from {}", input_dir);
+    let entries = collect_pack_entries(input_dir)?;
+
+    // Write next to the output, so a failed pack never leaves a truncated wad behind
+    let mut output_file = PartialFile::create(&output_path)?;
+    let packer = Packer::new(args.compression.to_chunk_compression());
+    let packed_count = packer.pack_entries(&entries, BufWriter::new(output_file.file()))?;
+    output_file.commit()?;
+
+    tracing::info!("packed {} chunks into {}", packed_count, output_path);
+
+    Ok(())
+}
+
+/// Constructs a sibling wad path named after the input directory,
+/// so that `Aatrox.wad` (as created by `extract`) packs back into `Aatrox.wad.client`
+fn default_output_path(input_dir: &Utf8Path) -> Utf8PathBuf {
+    let parent = input_dir.parent().unwrap_or(Utf8Path::new("."));
+    let name = input_dir.file_name().unwrap_or("packed");
+    if name.ends_with(".wad") {
+        parent.join(format!("{}.client", name))
+    } else {
+        parent.join(format!("{}.wad.client", name))
+    }
+}
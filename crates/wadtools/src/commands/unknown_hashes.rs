@@ -0,0 +1,143 @@
+use camino::Utf8Path;
+use convert_case::{Case, Casing};
+use eyre::{eyre, Context};
+use league_toolkit::{file::LeagueFileKind, wad::Wad};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+};
+use tracing_indicatif::span_ext::IndicatifSpanExt;
+use tracing_indicatif::style::ProgressStyle;
+
+use crate::{
+    chunk_kinds::ChunkKindCache,
+    utils::{
+        collect_input_wads, collect_wad_path_hashes, default_hashtable_dir, format_chunk_path_hash,
+        WadHashtable,
+    },
+};
+
+pub struct UnknownHashesArgs {
+    pub input: Vec<String>,
+    pub output: String,
+    pub hashtable: Option<String>,
+    pub hashtable_dir: Option<String>,
+    pub strict_hashtables: bool,
+    /// Also write the kind of each chunk, detected from the head of its data
+    pub kind: bool,
+    /// Also write the uncompressed size of each chunk
+    pub size: bool,
+}
+
+/// A chunk whose path hash couldn't be resolved, as found in the first wad containing it
+struct UnknownChunk {
+    kind: Option<LeagueFileKind>,
+    uncompressed_size: usize,
+}
+
+pub fn unknown_hashes(args: UnknownHashesArgs) -> eyre::Result<()> {
+    let wads = collect_input_wads(&args.input)?;
+    if wads.is_empty() {
+        return Err(eyre!(
+            "No wad files found in input: {}",
+            args.input.join(", ")
+        ));
+    }
+
+    let path_hashes = collect_wad_path_hashes(wads.iter().map(|wad| wad.path.as_path()))?;
+    let mut hashtable = WadHashtable::with_path_hashes(path_hashes)?;
+    hashtable.set_strict(args.strict_hashtables);
+    if let Some(dir_override) = &args.hashtable_dir {
+        hashtable.add_from_dir(Utf8Path::new(dir_override))?;
+    } else if let Some(dir) = default_hashtable_dir() {
+        hashtable.add_from_dir(dir)?;
+    }
+    if let Some(hashtable_path) = &args.hashtable {
+        tracing::info!("loading hashtable from {}", hashtable_path);
+        hashtable.add_from_file(Utf8Path::new(hashtable_path))?;
+    }
+
+    let span = tracing::info_span!("unknown_hashes", total = wads.len());
+    span.pb_set_style(
+        &ProgressStyle::with_template("{wide_bar:40.cyan/blue} {pos}/{len} \n {spinner} {msg}")
+            .unwrap(),
+    );
+    span.pb_set_length(wads.len() as u64);
+    span.pb_set_message("Scanning wads");
+    span.pb_set_finish_message("Scanning complete");
+
+    let mut unknown_chunks = BTreeMap::<u64, UnknownChunk>::new();
+    span.in_scope(|| {
+        for input_wad in &wads {
+            let source = File::open(input_wad.path.as_std_path())
+                .wrap_err(format!("failed to open wad: {}", input_wad.path))?;
+            let wad =
+                Wad::mount(&source).wrap_err(format!("failed to mount wad: {}", input_wad.path))?;
+            let mut kind_cache = args.kind.then(|| ChunkKindCache::load(&input_wad.path));
+            let mut reader = &source;
+
+            let mut chunks = wad
+                .chunks()
+                .values()
+                .filter(|chunk| {
+                    !hashtable.contains(chunk.path_hash)
+                        && !unknown_chunks.contains_key(&chunk.path_hash)
+                })
+                .collect::<Vec<_>>();
+            chunks.sort_by_key(|chunk| chunk.path_hash);
+
+            for chunk in chunks {
+                let kind = match &mut kind_cache {
+                    Some(kind_cache) => Some(
+                        kind_cache
+                            .identify(&mut reader, chunk)
+                            .wrap_err(format!("wad: {}", input_wad.path))?,
+                    ),
+                    None => None,
+                };
+
+                unknown_chunks.insert(
+                    chunk.path_hash,
+                    UnknownChunk {
+                        kind,
+                        uncompressed_size: chunk.uncompressed_size,
+                    },
+                );
+            }
+
+            if let Some(kind_cache) = &mut kind_cache {
+                if let Err(error) = kind_cache.save(wad.chunks()) {
+                    tracing::debug!("chunk kind cache not saved ({:#})", error);
+                }
+            }
+            span.pb_inc(1);
+        }
+
+        eyre::Ok(())
+    })?;
+
+    let output_file = File::create(&args.output)
+        .wrap_err(format!("failed to create output file: {}", args.output))?;
+    let mut writer = BufWriter::new(output_file);
+    for (path_hash, chunk) in &unknown_chunks {
+        write!(writer, "{}", format_chunk_path_hash(*path_hash))?;
+        if let Some(kind) = chunk.kind {
+            write!(writer, " {}", format!("{:?}", kind).to_case(Case::Snake))?;
+        }
+        if args.size {
+            write!(writer, " {}", chunk.uncompressed_size)?;
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+
+    tracing::info!(
+        "wrote {} unknown hashes from {} wads to {}",
+        unknown_chunks.len(),
+        wads.len(),
+        args.output
+    );
+
+    Ok(())
+}
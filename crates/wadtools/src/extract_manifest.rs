@@ -0,0 +1,455 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use eyre::Context;
+use league_toolkit::wad::WadChunk;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+};
+
+use crate::{
+    extractor::ExtractedChunk,
+    utils::{format_chunk_path_hash, PartialFile},
+};
+
+/// Name of the manifest file written into the output directory by `extract --incremental`
+pub const EXTRACT_MANIFEST_FILE_NAME: &str = ".wadtools-manifest.json";
+
+const EXTRACT_MANIFEST_VERSION: u32 = 2;
+/// Version 1 manifests only stored the checksum of each chunk
+const EXTRACT_MANIFEST_VERSION_CHECKSUM_ONLY: u32 = 1;
+
+/// Tracks which chunk was extracted to which file, so re-extracting can skip unchanged chunks
+/// and clean up the files of chunks that no longer exist
+#[derive(Debug, Default)]
+pub struct ExtractManifest {
+    chunks: HashMap<u64, ManifestChunk>,
+}
+
+/// A chunk as it was extracted
+#[derive(Debug, Clone, PartialEq)]
+struct ManifestChunk {
+    checksum: u64,
+    compressed_size: usize,
+    uncompressed_size: usize,
+    compression_type: u8,
+    /// Output path relative to the output directory
+    path: Utf8PathBuf,
+}
+
+impl ManifestChunk {
+    fn new(chunk: &WadChunk, path: Utf8PathBuf) -> Self {
+        Self {
+            checksum: chunk.checksum,
+            compressed_size: chunk.compressed_size,
+            uncompressed_size: chunk.uncompressed_size,
+            compression_type: chunk.compression_type as u8,
+            path,
+        }
+    }
+
+    /// Whether the chunk still has the same contents. A zero checksum is not trusted, wads
+    /// without checksums store zero for every chunk.
+    fn matches(&self, chunk: &WadChunk) -> bool {
+        self.checksum != 0
+            && self.checksum == chunk.checksum
+            && self.compressed_size == chunk.compressed_size
+            && self.uncompressed_size == chunk.uncompressed_size
+            && self.compression_type == chunk.compression_type as u8
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExtractManifestFile {
+    version: u32,
+    chunks: Vec<ExtractManifestEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExtractManifestEntry {
+    path_hash: String,
+    checksum: String,
+    #[serde(default)]
+    compressed_size: usize,
+    #[serde(default)]
+    uncompressed_size: usize,
+    #[serde(default)]
+    compression_type: u8,
+    path: String,
+}
+
+impl ExtractManifest {
+    /// Loads the manifest from the output directory, or returns an empty one if there is none
+    pub fn load(output_dir: &Utf8Path) -> eyre::Result<Self> {
+        let manifest_path = output_dir.join(EXTRACT_MANIFEST_FILE_NAME);
+        let contents = match fs::read_to_string(manifest_path.as_std_path()) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => {
+                return Err(error)
+                    .wrap_err(format!("failed to read manifest (path: {})", manifest_path))
+            }
+        };
+
+        let file: ExtractManifestFile = serde_json::from_str(&contents).wrap_err(format!(
+            "failed to parse manifest (path: {})",
+            manifest_path
+        ))?;
+        if file.version != EXTRACT_MANIFEST_VERSION
+            && file.version != EXTRACT_MANIFEST_VERSION_CHECKSUM_ONLY
+        {
+            tracing::warn!(
+                "ignoring manifest with unsupported version {} (path: {})",
+                file.version,
+                manifest_path
+            );
+            return Ok(Self::default());
+        }
+
+        let mut chunks = HashMap::with_capacity(file.chunks.len());
+        for entry in file.chunks {
+            let path_hash = u64::from_str_radix(&entry.path_hash, 16).wrap_err(format!(
+                "invalid path hash in manifest: {}",
+                entry.path_hash
+            ))?;
+            let checksum = u64::from_str_radix(&entry.checksum, 16)
+                .wrap_err(format!("invalid checksum in manifest: {}", entry.checksum))?;
+            let chunk = ManifestChunk {
+                // Without the sizes a matching checksum isn't enough, so the chunks of old
+                // manifests are extracted once more. Their paths are kept to clean up moved files.
+                checksum: match file.version {
+                    EXTRACT_MANIFEST_VERSION_CHECKSUM_ONLY => 0,
+                    _ => checksum,
+                },
+                compressed_size: entry.compressed_size,
+                uncompressed_size: entry.uncompressed_size,
+                compression_type: entry.compression_type,
+                path: Utf8PathBuf::from(entry.path),
+            };
+            chunks.insert(path_hash, chunk);
+        }
+
+        Ok(Self { chunks })
+    }
+
+    pub fn save(&self, output_dir: &Utf8Path) -> eyre::Result<()> {
+        let mut chunks = self
+            .chunks
+            .iter()
+            .map(|(path_hash, chunk)| ExtractManifestEntry {
+                path_hash: format_chunk_path_hash(*path_hash),
+                checksum: format_chunk_path_hash(chunk.checksum),
+                compressed_size: chunk.compressed_size,
+                uncompressed_size: chunk.uncompressed_size,
+                compression_type: chunk.compression_type,
+                path: chunk.path.to_string(),
+            })
+            .collect::<Vec<_>>();
+        chunks.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let file = ExtractManifestFile {
+            version: EXTRACT_MANIFEST_VERSION,
+            chunks,
+        };
+
+        fs::create_dir_all(output_dir.as_std_path())?;
+        let manifest_path = output_dir.join(EXTRACT_MANIFEST_FILE_NAME);
+        // Replaced at once, an interrupted save must not leave a manifest that fails to load
+        let mut manifest_file = PartialFile::create(&manifest_path)?;
+        serde_json::to_writer_pretty(manifest_file.file(), &file).wrap_err(format!(
+            "failed to write manifest (path: {})",
+            manifest_path
+        ))?;
+        manifest_file.commit()?;
+
+        Ok(())
+    }
+
+    /// Whether the chunk was extracted with the same contents and its file still exists
+    pub fn is_up_to_date(&self, chunk: &WadChunk, output_dir: &Utf8Path) -> bool {
+        self.chunks.get(&chunk.path_hash).is_some_and(|recorded| {
+            recorded.matches(chunk) && output_dir.join(&recorded.path).is_file()
+        })
+    }
+
+    /// Output path the chunk was extracted to, relative to the output directory
+    pub fn output_path(&self, path_hash: u64) -> Option<&Utf8Path> {
+        self.chunks
+            .get(&path_hash)
+            .map(|recorded| recorded.path.as_path())
+    }
+
+    /// Forgets a chunk and deletes its file
+    pub fn remove(&mut self, path_hash: u64, output_dir: &Utf8Path) -> eyre::Result<()> {
+        match self.chunks.remove(&path_hash) {
+            Some(recorded) => remove_output_file(output_dir, &recorded.path),
+            None => Ok(()),
+        }
+    }
+
+    /// Records freshly extracted chunks, removing their previous files if they moved
+    pub fn record(
+        &mut self,
+        extracted_chunks: &[ExtractedChunk],
+        output_dir: &Utf8Path,
+    ) -> eyre::Result<()> {
+        let mut previous_paths = Vec::new();
+        for extracted in extracted_chunks {
+            let recorded = ManifestChunk::new(&extracted.chunk, extracted.path.clone());
+            if let Some(previous) = self.chunks.insert(extracted.chunk.path_hash, recorded) {
+                if previous.path != extracted.path {
+                    previous_paths.push(previous.path);
+                }
+            }
+        }
+
+        // A moved chunk's previous file can be the new file of another chunk by now
+        if !previous_paths.is_empty() {
+            let output_paths = self
+                .chunks
+                .values()
+                .map(|recorded| recorded.path.as_path())
+                .collect::<HashSet<_>>();
+            for previous_path in &previous_paths {
+                if !output_paths.contains(previous_path.as_path()) {
+                    remove_output_file(output_dir, previous_path)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the files of all chunks that are not in `present_chunks` anymore.
+    /// Returns the number of removed chunks.
+    pub fn remove_missing(
+        &mut self,
+        present_chunks: &HashSet<u64>,
+        output_dir: &Utf8Path,
+    ) -> eyre::Result<usize> {
+        let missing_chunks = self
+            .chunks
+            .keys()
+            .filter(|path_hash| !present_chunks.contains(path_hash))
+            .copied()
+            .collect::<Vec<_>>();
+
+        for path_hash in &missing_chunks {
+            if let Some(recorded) = self.chunks.remove(path_hash) {
+                tracing::debug!("removing {}", recorded.path);
+                remove_output_file(output_dir, &recorded.path)?;
+            }
+        }
+
+        Ok(missing_chunks.len())
+    }
+}
+
+/// Removes an extracted file along with the directories it leaves empty
+fn remove_output_file(output_dir: &Utf8Path, path: &Utf8Path) -> eyre::Result<()> {
+    let full_path = output_dir.join(path);
+    // The file was already replaced by the directory of another chunk
+    if full_path.is_dir() {
+        return Ok(());
+    }
+    match fs::remove_file(full_path.as_std_path()) {
+        Ok(()) => {}
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => {
+            return Err(error).wrap_err(format!("failed to remove file (path: {})", full_path))
+        }
+    }
+
+    for ancestor in path.ancestors().skip(1) {
+        if ancestor.as_str().is_empty()
+            || fs::remove_dir(output_dir.join(ancestor).as_std_path()).is_err()
+        {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use league_toolkit::wad::WadChunkCompression;
+
+    fn wad_chunk(path_hash: u64, checksum: u64) -> WadChunk {
+        WadChunk {
+            path_hash,
+            data_offset: 0,
+            compressed_size: 16,
+            uncompressed_size: 32,
+            compression_type: WadChunkCompression::Zstd,
+            is_duplicated: false,
+            frame_count: 0,
+            start_frame: 0,
+            checksum,
+        }
+    }
+
+    /// Writes the file of an extracted chunk and returns it
+    fn extract(output_dir: &Utf8Path, chunk: WadChunk, path: &str) -> ExtractedChunk {
+        let full_path = output_dir.join(path);
+        fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+        fs::write(full_path, path).unwrap();
+
+        ExtractedChunk {
+            chunk,
+            path: Utf8PathBuf::from(path),
+        }
+    }
+
+    #[test]
+    fn is_up_to_date_requires_the_same_chunk_and_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_dir = Utf8Path::from_path(temp_dir.path()).unwrap();
+        let chunk = wad_chunk(1, 0xabc);
+
+        let mut manifest = ExtractManifest::default();
+        assert!(!manifest.is_up_to_date(&chunk, output_dir));
+        manifest
+            .record(&[extract(output_dir, chunk, "data/a.bin")], output_dir)
+            .unwrap();
+        assert!(manifest.is_up_to_date(&chunk, output_dir));
+
+        let changed_chunks = [
+            wad_chunk(1, 0xdef),
+            WadChunk {
+                compressed_size: 17,
+                ..chunk
+            },
+            WadChunk {
+                uncompressed_size: 33,
+                ..chunk
+            },
+            WadChunk {
+                compression_type: WadChunkCompression::GZip,
+                ..chunk
+            },
+        ];
+        for changed in &changed_chunks {
+            assert!(
+                !manifest.is_up_to_date(changed, output_dir),
+                "{:?}",
+                changed
+            );
+        }
+
+        fs::remove_file(output_dir.join("data/a.bin")).unwrap();
+        assert!(!manifest.is_up_to_date(&chunk, output_dir));
+    }
+
+    #[test]
+    fn zero_checksum_is_never_up_to_date() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_dir = Utf8Path::from_path(temp_dir.path()).unwrap();
+        let chunk = wad_chunk(1, 0);
+
+        let mut manifest = ExtractManifest::default();
+        manifest
+            .record(&[extract(output_dir, chunk, "a.bin")], output_dir)
+            .unwrap();
+        assert!(!manifest.is_up_to_date(&chunk, output_dir));
+    }
+
+    #[test]
+    fn record_removes_the_previous_file_of_moved_chunks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_dir = Utf8Path::from_path(temp_dir.path()).unwrap();
+        let (a, b) = (wad_chunk(1, 0xa), wad_chunk(2, 0xb));
+
+        let mut manifest = ExtractManifest::default();
+        manifest
+            .record(
+                &[
+                    extract(output_dir, a, "old/a.bin"),
+                    extract(output_dir, b, "b.ltk"),
+                ],
+                output_dir,
+            )
+            .unwrap();
+
+        // `a` moves away, `b` moves to the file `a` left behind
+        manifest
+            .record(
+                &[
+                    extract(output_dir, a, "new/a.bin"),
+                    extract(output_dir, b, "old/a.bin"),
+                ],
+                output_dir,
+            )
+            .unwrap();
+
+        assert!(output_dir.join("new/a.bin").is_file());
+        assert!(output_dir.join("old/a.bin").is_file());
+        assert!(!output_dir.join("b.ltk").exists());
+        assert_eq!(manifest.output_path(1), Some(Utf8Path::new("new/a.bin")));
+        assert_eq!(manifest.output_path(2), Some(Utf8Path::new("old/a.bin")));
+    }
+
+    #[test]
+    fn remove_missing_deletes_files_and_empty_directories() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_dir = Utf8Path::from_path(temp_dir.path()).unwrap();
+        let (a, b, c) = (wad_chunk(1, 0xa), wad_chunk(2, 0xb), wad_chunk(3, 0xc));
+
+        let mut manifest = ExtractManifest::default();
+        manifest
+            .record(
+                &[
+                    extract(output_dir, a, "kept/a.bin"),
+                    extract(output_dir, b, "kept/b.bin"),
+                    extract(output_dir, c, "gone/deeper/c.bin"),
+                ],
+                output_dir,
+            )
+            .unwrap();
+
+        let removed_count = manifest
+            .remove_missing(&HashSet::from([1]), output_dir)
+            .unwrap();
+        assert_eq!(removed_count, 2);
+        assert!(output_dir.join("kept/a.bin").is_file());
+        assert!(!output_dir.join("kept/b.bin").exists());
+        assert!(!output_dir.join("gone").exists());
+        assert_eq!(manifest.output_path(2), None);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_dir = Utf8Path::from_path(temp_dir.path()).unwrap();
+        let chunk = wad_chunk(1, 0xabc);
+
+        let mut manifest = ExtractManifest::default();
+        manifest
+            .record(&[extract(output_dir, chunk, "a.bin")], output_dir)
+            .unwrap();
+        manifest.save(output_dir).unwrap();
+
+        let loaded = ExtractManifest::load(output_dir).unwrap();
+        assert_eq!(loaded.chunks, manifest.chunks);
+        assert!(loaded.is_up_to_date(&chunk, output_dir));
+        assert_eq!(fs::read_dir(output_dir.as_std_path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn checksum_only_manifests_are_stale() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_dir = Utf8Path::from_path(temp_dir.path()).unwrap();
+        let chunk = wad_chunk(1, 0xabc);
+        extract(output_dir, chunk, "a.bin");
+        fs::write(
+            output_dir.join(EXTRACT_MANIFEST_FILE_NAME),
+            r#"{"version":1,"chunks":[{"path_hash":"0000000000000001","checksum":"0000000000000abc","path":"a.bin"}]}"#,
+        )
+        .unwrap();
+
+        let manifest = ExtractManifest::load(output_dir).unwrap();
+        assert!(!manifest.is_up_to_date(&chunk, output_dir));
+        assert_eq!(manifest.output_path(1), Some(Utf8Path::new("a.bin")));
+    }
+}
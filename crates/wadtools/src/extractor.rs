@@ -1,37 +1,50 @@
-use crate::utils::{is_hex_chunk_path, truncate_middle, WadHashtable};
+use crate::{
+    packer::{parse_hex_chunk_file_name, split_ltk_file_name},
+    utils::{is_hex_chunk_path, truncate_middle, WadHashtable},
+};
 use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::eyre::{self, Ok};
 use eyre::Context;
 use fancy_regex::Regex;
 use league_toolkit::{
     file::LeagueFileKind,
-    wad::{WadChunk, WadDecoder},
+    wad::{Wad, WadChunk, WadDecoder},
 };
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::{self, File},
     io::{self, Read, Seek},
+    panic,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    thread,
 };
 use tracing_indicatif::span_ext::IndicatifSpanExt;
 use tracing_indicatif::style::ProgressStyle;
 
 const MAX_LOG_PATH_LEN: usize = 120;
 
+/// A chunk that was written to disk
+#[derive(Debug, Clone)]
+pub struct ExtractedChunk {
+    pub chunk: WadChunk,
+    /// Output path relative to the extract directory
+    pub path: Utf8PathBuf,
+}
+
 pub struct Extractor<'chunks> {
-    decoder: &'chunks mut WadDecoder<'chunks, &'chunks File>,
+    wad_path: &'chunks Utf8Path,
     hashtable: &'chunks WadHashtable,
     filter_pattern: Option<Regex>,
+    jobs: usize,
 }
 
 impl<'chunks> Extractor<'chunks> {
-    pub fn new(
-        decoder: &'chunks mut WadDecoder<'chunks, &'chunks File>,
-        hashtable: &'chunks WadHashtable,
-    ) -> Self {
+    pub fn new(wad_path: &'chunks Utf8Path, hashtable: &'chunks WadHashtable) -> Self {
         Self {
-            decoder,
+            wad_path,
             hashtable,
             filter_pattern: None,
+            jobs: 1,
         }
     }
 
@@ -39,12 +52,18 @@ impl<'chunks> Extractor<'chunks> {
         self.filter_pattern = filter_pattern;
     }
 
+    /// Sets the number of worker threads, each worker reads the wad through its own file handle
+    pub fn set_jobs(&mut self, jobs: usize) {
+        self.jobs = jobs.max(1);
+    }
+
     pub fn extract_chunks(
-        &mut self,
+        &self,
         chunks: &HashMap<u64, WadChunk>,
+        chunk_directories: &ChunkDirectories,
         extract_directory: impl AsRef<Utf8Path>,
         filter_type: Option<&[LeagueFileKind]>,
-    ) -> eyre::Result<usize> {
+    ) -> eyre::Result<Vec<ExtractedChunk>> {
         let total = chunks.len() as u64;
         let span = tracing::info_span!("extract", total = total);
         let _entered = span.enter();
@@ -57,14 +76,13 @@ impl<'chunks> Extractor<'chunks> {
         span.pb_set_finish_message("Extraction complete");
 
         extract_wad_chunks(
-            self.decoder,
+            self.wad_path,
             chunks,
+            chunk_directories,
             self.hashtable,
             extract_directory.as_ref().to_path_buf(),
-            |progress, message| {
-                // progress is 0.0..1.0; convert to absolute position
-                let position = (progress * total as f64).round() as u64;
-                span.pb_set_position(position);
+            |message| {
+                span.pb_inc(1);
                 if let Some(msg) = message {
                     span.pb_set_message(msg);
                 }
@@ -72,44 +90,172 @@ impl<'chunks> Extractor<'chunks> {
             },
             filter_type,
             self.filter_pattern.as_ref(),
+            self.jobs,
         )
     }
 }
 
-pub fn extract_wad_chunks<TSource: Read + Seek>(
-    decoder: &mut WadDecoder<TSource>,
+/// Extracts the chunks of the wad at `wad_path` using `jobs` worker threads.
+///
+/// `report_progress` is called once for every chunk, including the ones that are filtered out.
+#[allow(clippy::too_many_arguments)]
+pub fn extract_wad_chunks(
+    wad_path: &Utf8Path,
     chunks: &HashMap<u64, WadChunk>,
+    chunk_directories: &ChunkDirectories,
     wad_hashtable: &WadHashtable,
     extract_directory: Utf8PathBuf,
-    report_progress: impl Fn(f64, Option<&str>) -> eyre::Result<()>,
+    report_progress: impl Fn(Option<&str>) -> eyre::Result<()> + Sync,
     filter_type: Option<&[LeagueFileKind]>,
     filter_pattern: Option<&Regex>,
-) -> eyre::Result<usize> {
-    let mut i = 0;
-    let mut extracted_count = 0;
+    jobs: usize,
+) -> eyre::Result<Vec<ExtractedChunk>> {
+    let mut selected_chunks = Vec::with_capacity(chunks.len());
     for chunk in chunks.values() {
-        let chunk_path_str = wad_hashtable.resolve_path(chunk.path_hash());
-        let chunk_path = Utf8Path::new(chunk_path_str.as_ref());
-
-        // advance progress for every chunk (including ones we skip)
-        let truncated = truncate_middle(chunk_path_str.as_ref(), MAX_LOG_PATH_LEN);
-        report_progress(i as f64 / chunks.len() as f64, Some(truncated.as_str()))?;
+        let chunk_path = wad_hashtable.resolve_path(chunk.path_hash());
 
         if let Some(regex) = filter_pattern {
-            if !regex.is_match(chunk_path_str.as_ref()).unwrap_or(false) {
-                i += 1;
+            if !regex.is_match(chunk_path.as_ref()).unwrap_or(false) {
+                report_progress(None)?;
                 continue;
             }
         }
 
-        if extract_wad_chunk(decoder, chunk, chunk_path, &extract_directory, filter_type)? {
-            extracted_count += 1;
+        selected_chunks.push((chunk, chunk_path));
+    }
+    // Sorted so the work order doesn't depend on hash map iteration order
+    selected_chunks.sort_by(|(_, a), (_, b)| a.cmp(b));
+
+    let next_chunk = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let extract_worker = || -> eyre::Result<Vec<ExtractedChunk>> {
+        let source = File::open(wad_path.as_std_path())
+            .wrap_err(format!("failed to open wad (path: {})", wad_path))?;
+        let mut wad = Wad::mount(&source)?;
+        let (mut decoder, _) = wad.decode();
+
+        let mut extracted_chunks = Vec::new();
+        while !failed.load(Ordering::Relaxed) {
+            let Some((chunk, chunk_path)) =
+                selected_chunks.get(next_chunk.fetch_add(1, Ordering::Relaxed))
+            else {
+                break;
+            };
+
+            let output_path = extract_wad_chunk(
+                &mut decoder,
+                chunk,
+                Utf8Path::new(chunk_path.as_ref()),
+                &extract_directory,
+                chunk_directories,
+                filter_type,
+            )
+            .inspect_err(|_| failed.store(true, Ordering::Relaxed))?;
+            if let Some(output_path) = output_path {
+                extracted_chunks.push(ExtractedChunk {
+                    chunk: **chunk,
+                    path: output_path,
+                });
+            }
+
+            let truncated = truncate_middle(chunk_path.as_ref(), MAX_LOG_PATH_LEN);
+            report_progress(Some(truncated.as_str()))?;
+        }
+
+        Ok(extracted_chunks)
+    };
+
+    let worker_count = jobs.clamp(1, selected_chunks.len().max(1));
+    let mut extracted_chunks = thread::scope(|scope| {
+        let workers = (0..worker_count)
+            .map(|_| scope.spawn(extract_worker))
+            .collect::<Vec<_>>();
+
+        workers
+            .into_iter()
+            // Re-raise a worker panic as is, with its original message and location
+            .map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| panic::resume_unwind(panic))
+            })
+            .collect::<eyre::Result<Vec<_>>>()
+    })?
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+    extracted_chunks.sort_by_key(|extracted| extracted.chunk.path_hash);
+
+    Ok(extracted_chunks)
+}
+
+/// Every directory the chunks of a wad are extracted into.
+///
+/// Chunks whose path is also one of these directories get the `.ltk` treatment. This is collected
+/// over all chunks of the wad rather than the ones being extracted, so a chunk gets the same name
+/// no matter which filters select it, and independent of the order chunks are written in.
+#[derive(Debug, Default)]
+pub struct ChunkDirectories {
+    directories: HashSet<Utf8PathBuf>,
+}
+
+impl ChunkDirectories {
+    pub fn collect(chunks: &HashMap<u64, WadChunk>, wad_hashtable: &WadHashtable) -> Self {
+        let mut directories = HashSet::new();
+        for chunk in chunks.values() {
+            let chunk_path = wad_hashtable.resolve_path(chunk.path_hash);
+            for ancestor in Utf8Path::new(chunk_path.as_ref()).ancestors().skip(1) {
+                if ancestor.as_str().is_empty() || directories.contains(ancestor) {
+                    break;
+                }
+                directories.insert(ancestor.to_path_buf());
+            }
+        }
+
+        Self { directories }
+    }
+
+    pub fn contains(&self, path: &Utf8Path) -> bool {
+        self.directories.contains(path)
+    }
+
+    /// Whether a chunk that was extracted to `output_path` before would be written there again.
+    ///
+    /// Only the parts of the name that don't depend on the chunk data are checked, so this holds
+    /// as long as the chunk didn't change.
+    pub fn keeps_output_path(
+        &self,
+        chunk: &WadChunk,
+        chunk_path: &Utf8Path,
+        output_path: &Utf8Path,
+        extract_directory: &Utf8Path,
+    ) -> bool {
+        // Hashed and long filename chunks are named after the path hash
+        if is_hex_chunk_path(chunk_path)
+            || parse_hex_chunk_file_name(output_path.as_str()) == Some(chunk.path_hash)
+        {
+            return true;
         }
 
-        i += 1;
+        let has_ltk_name = output_path.parent() == chunk_path.parent()
+            && output_path
+                .file_name()
+                .and_then(split_ltk_file_name)
+                .is_some_and(|(name, _)| Some(name) == chunk_path.file_name());
+        has_ltk_name == self.needs_ltk_name(chunk_path, extract_directory)
     }
 
-    Ok(extracted_count)
+    /// Whether a chunk is written as `<file name>.ltk[.<ext>]` instead of its own path:
+    /// - if the original path has no extension
+    /// - OR if it collides with a directory of another chunk, or one that existed before extraction
+    /// - OR if the original file name already looks like a `.ltk` name, so `pack` only strips the
+    ///   `.ltk` added here
+    fn needs_ltk_name(&self, chunk_path: &Utf8Path, extract_directory: &Utf8Path) -> bool {
+        chunk_path.extension().is_none()
+            || self.contains(chunk_path)
+            || extract_directory.join(chunk_path).is_dir()
+            || split_ltk_file_name(chunk_path.file_name().unwrap_or("")).is_some()
+    }
 }
 
 pub fn extract_wad_chunk<'wad, TSource: Read + Seek>(
@@ -117,8 +263,9 @@ pub fn extract_wad_chunk<'wad, TSource: Read + Seek>(
     chunk: &WadChunk,
     chunk_path: impl AsRef<Utf8Path>,
     extract_directory: impl AsRef<Utf8Path>,
+    chunk_directories: &ChunkDirectories,
     filter_type: Option<&[LeagueFileKind]>,
-) -> eyre::Result<bool> {
+) -> eyre::Result<Option<Utf8PathBuf>> {
     let chunk_data = decoder.load_chunk_decompressed(chunk).wrap_err(format!(
         "failed to decompress chunk (chunk_path: {})",
         chunk_path.as_ref().as_str()
@@ -131,29 +278,34 @@ pub fn extract_wad_chunk<'wad, TSource: Read + Seek>(
             chunk_path.as_ref().as_str(),
             chunk_kind
         );
-        return Ok(false);
+        return Ok(None);
     }
 
-    let chunk_path =
-        resolve_final_chunk_path(&extract_directory, chunk_path, &chunk_data, chunk_kind);
+    let chunk_path = resolve_final_chunk_path(
+        &extract_directory,
+        chunk_path,
+        chunk_directories,
+        &chunk_data,
+        chunk_kind,
+    );
     let full_path = extract_directory.as_ref().join(&chunk_path);
     if let Some(parent) = full_path.parent() {
         fs::create_dir_all(parent.as_std_path())?;
     }
     let Err(error) = fs::write(full_path.as_std_path(), &chunk_data) else {
-        return Ok(true);
+        return Ok(Some(chunk_path));
     };
 
     // This will happen if the filename is too long
     if error.kind() == io::ErrorKind::InvalidFilename {
-        write_long_filename_chunk(
+        let hashed_path = write_long_filename_chunk(
             chunk,
             chunk_path,
             extract_directory,
             &chunk_data,
             chunk_kind,
         )?;
-        Ok(true)
+        Ok(Some(hashed_path))
     } else {
         Err(error).wrap_err(format!(
             "failed to write chunk (chunk_path: {})",
@@ -165,6 +317,7 @@ pub fn extract_wad_chunk<'wad, TSource: Read + Seek>(
 fn resolve_final_chunk_path(
     extract_directory: impl AsRef<Utf8Path>,
     chunk_path: impl AsRef<Utf8Path>,
+    chunk_directories: &ChunkDirectories,
     chunk_data: &[u8],
     chunk_kind: LeagueFileKind,
 ) -> Utf8PathBuf {
@@ -178,24 +331,21 @@ fn resolve_final_chunk_path(
         return final_path;
     }
 
-    // - If the original path has no extension, affix .ltk (and real extension if known)
-    // - OR if the destination path collides with an existing directory, affix .ltk
-    let has_extension = final_path.extension().is_some();
-    let collides_with_dir = extract_directory.as_ref().join(&final_path).is_dir();
-    if !has_extension || collides_with_dir {
-        let original_stem = chunk_path.as_ref().file_stem().unwrap_or("");
-        let new_name = build_ltk_name(original_stem, chunk_data);
+    if chunk_directories.needs_ltk_name(&final_path, extract_directory.as_ref()) {
+        // The whole original name is kept in front of `.ltk`, so `pack` can restore it
+        let original_name = chunk_path.as_ref().file_name().unwrap_or("");
+        let new_name = build_ltk_name(original_name, chunk_data);
         final_path.set_file_name(&new_name);
     }
 
     final_path
 }
 
-fn build_ltk_name(file_stem: &str, chunk_data: &[u8]) -> String {
+fn build_ltk_name(file_name: &str, chunk_data: &[u8]) -> String {
     let kind = LeagueFileKind::identify_from_bytes(chunk_data);
     match kind.extension() {
-        Some(ext) => format!("{}.ltk.{}", file_stem, ext),
-        None => format!("{}.ltk", file_stem),
+        Some(ext) => format!("{}.ltk.{}", file_name, ext),
+        None => format!("{}.ltk", file_name),
     }
 }
 
@@ -205,7 +355,7 @@ fn write_long_filename_chunk(
     extract_directory: impl AsRef<Utf8Path>,
     chunk_data: &[u8],
     chunk_kind: LeagueFileKind,
-) -> eyre::Result<()> {
+) -> eyre::Result<Utf8PathBuf> {
     let mut hashed_path = Utf8PathBuf::from(format!("{:016x}", chunk.path_hash()));
     if let Some(ext) = chunk_kind.extension() {
         hashed_path.set_extension(ext);
@@ -220,9 +370,9 @@ fn write_long_filename_chunk(
     );
 
     fs::write(
-        extract_directory.as_ref().join(hashed_path).as_std_path(),
+        extract_directory.as_ref().join(&hashed_path).as_std_path(),
         chunk_data,
     )?;
 
-    Ok(())
+    Ok(hashed_path)
 }
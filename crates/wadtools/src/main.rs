@@ -16,8 +16,12 @@ use tracing_subscriber::{filter, fmt};
 use utils::config::{default_config_path, load_or_create_config, resolve_and_persist_progress};
 use utils::default_hashtable_dir;
 
+mod bin_diff;
+mod chunk_kinds;
 mod commands;
+mod extract_manifest;
 mod extractor;
+mod packer;
 mod utils;
 
 use commands::*;
@@ -74,6 +78,10 @@ struct Args {
     #[arg(long, value_name = "DIR")]
     hashtable_dir: Option<String>,
 
+    /// Fail on malformed hashtable lines instead of skipping them with a warning
+    #[arg(long)]
+    strict_hashtables: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -83,14 +91,29 @@ pub enum Commands {
     /// Extract the contents of a wad file
     #[command(visible_alias = "e")]
     Extract {
-        /// Path to the input wad file
-        #[arg(short, long, required_unless_present = "list_filters")]
-        input: Option<String>,
+        /// Wad files, glob patterns or directories (searched recursively for wad files)
+        #[arg(short, long, required_unless_present = "list_filters", num_args = 1..)]
+        input: Vec<String>,
 
         /// Path to the output directory
+        ///
+        /// When extracting multiple wads, each wad is extracted into its own subfolder
         #[arg(short, long)]
         output: Option<String>,
 
+        /// Extract all wads into the output directory instead of one subfolder per wad
+        #[arg(long)]
+        merge: bool,
+
+        /// What to do with chunks that exist with different contents in multiple merged wads
+        #[arg(long, value_enum, default_value_t = ConflictPolicy::Skip, requires = "merge")]
+        on_conflict: ConflictPolicy,
+
+        /// Only extract chunks that changed since the last incremental extraction and delete
+        /// the files of chunks that were removed, tracked by a manifest in the output directory
+        #[arg(long)]
+        incremental: bool,
+
         /// Path to the hashtable file
         #[arg(short = 'H', long, visible_short_alias = 'd')]
         hashtable: Option<String>,
@@ -117,19 +140,75 @@ pub enum Commands {
             help = "Only extract chunks whose resolved path matches this regex (case-insensitive by default; use (?-i) to disable)"
         )]
         pattern: Option<String>,
+
+        /// Number of worker threads (defaults to the number of available cores)
+        #[arg(short, long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        jobs: Option<u64>,
+    },
+    /// Pack a directory into a wad file
+    ///
+    /// Walks the input directory and packs every file into a wad chunk.
+    /// Understands the naming conventions produced by `extract`, so extracted wads can be rebuilt.
+    #[command(visible_alias = "p")]
+    Pack {
+        /// Path to the input directory
+        #[arg(short, long)]
+        input: String,
+
+        /// Path to the output wad file (defaults to a sibling `<input>.wad.client`)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Compression used for chunk data
+        #[arg(short, long, value_enum, default_value_t = PackCompression::Auto)]
+        compression: PackCompression,
+    },
+    /// Add, replace or remove chunks of an existing wad file
+    ///
+    /// Untouched chunks are copied verbatim without being recompressed.
+    /// Chunks can be named by their path or by their 16-hex path hash.
+    Edit {
+        /// Path to the input wad file
+        #[arg(short, long)]
+        input: String,
+
+        /// Path to the output wad file (defaults to overwriting the input)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Add a new chunk from a file
+        #[arg(short, long, value_name = "PATH=FILE", value_parser = parse_chunk_source)]
+        add: Vec<ChunkSource>,
+
+        /// Replace the data of an existing chunk with a file
+        #[arg(short, long, value_name = "PATH=FILE", value_parser = parse_chunk_source)]
+        replace: Vec<ChunkSource>,
+
+        /// Remove an existing chunk
+        #[arg(long, value_name = "PATH|HASH")]
+        remove: Vec<String>,
+
+        /// Compression used for added and replaced chunks
+        #[arg(short, long, value_enum, default_value_t = PackCompression::Auto)]
+        compression: PackCompression,
+
+        /// Path to the hashtable file
+        #[arg(short = 'H', long, visible_short_alias = 'd')]
+        hashtable: Option<String>,
     },
     /// Compare two wad files
     ///
     /// This command compares two wad files and prints the differences between them.
     /// Using the reference wad file, it will print the differences between the target wad file.
+    /// When given two directories, it pairs their wad files by relative path and diffs every pair.
     ///
     /// #[command(visible_alias = "d")]
     Diff {
-        /// Path to the reference wad file
+        /// Path to the reference wad file, or a directory of wad files
         #[arg(short, long)]
         reference: String,
 
-        /// Path to the target wad file
+        /// Path to the target wad file, or a directory of wad files
         #[arg(short, long)]
         target: String,
 
@@ -137,9 +216,51 @@ pub enum Commands {
         #[arg(short = 'H', long, visible_short_alias = 'd')]
         hashtable: Option<String>,
 
-        /// Output the diffs to a .csv file
-        #[arg(short, long, help = "The path to the output .csv file")]
+        /// Write the diffs to a file instead of stdout (CSV unless --format is given)
+        #[arg(short, long, help = "The path to the output file")]
         output: Option<String>,
+
+        /// Output format (defaults to table, or csv when writing to a file)
+        #[arg(short = 'F', long, value_enum)]
+        format: Option<DiffOutputFormat>,
+
+        /// Decompress modified chunks and compare their contents
+        #[arg(short, long)]
+        content: bool,
+
+        /// Additional bin hashtable files used to resolve property bin names in --content mode
+        #[arg(long, value_name = "FILE")]
+        bin_hashtable: Vec<String>,
+    },
+    /// Compute the hash of paths and names, or look hashes up in the hashtables
+    ///
+    /// Values are read from stdin (one per line) when none are given.
+    /// Prints `<hash> <value>` lines, the same format hashtables use.
+    #[command(visible_alias = "h")]
+    Hash {
+        /// Paths or names to hash (hashes to look up with --reverse)
+        values: Vec<String>,
+
+        /// Kind of hash
+        #[arg(short, long, value_enum, default_value_t = HashKind::Wad)]
+        kind: HashKind,
+
+        /// Look the given hashes up in the hashtables instead of hashing them
+        #[arg(short, long)]
+        reverse: bool,
+
+        /// Path to an additional hashtable file (a bin hashtable for fnv1a)
+        #[arg(short = 'H', long, visible_short_alias = 'd')]
+        hashtable: Option<String>,
+    },
+    /// Merge, dedupe, validate, diff and inspect hashtable files
+    ///
+    /// Checks that every name hashes to its stated hash and reports conflicts, where the same
+    /// hash maps to different names (when loading, the last one silently wins).
+    #[command(visible_alias = "ht")]
+    Hashtable {
+        #[command(subcommand)]
+        command: HashtableCommands,
     },
     /// Print the default hashtable directory
     #[command(visible_alias = "hd")]
@@ -181,15 +302,216 @@ pub enum Commands {
         #[arg(short = 'F', long, value_enum, default_value_t = ListOutputFormat::Table)]
         format: ListOutputFormat,
 
+        /// Only print the directories down to this depth (tree format only), like `du -d`
+        #[arg(long, value_name = "N")]
+        depth: Option<usize>,
+
+        /// Detect file types from the chunk contents instead of the path extension, like
+        /// `extract` does (cached per wad, so repeated listings are cheap)
+        #[arg(long)]
+        detect: bool,
+
+        /// Only list chunks with one of these compressions
+        #[arg(long, value_enum, value_delimiter = ',', num_args = 1..)]
+        compression: Option<Vec<ListCompression>>,
+
+        /// Only list chunks flagged as duplicates of another chunk's data
+        #[arg(long)]
+        duplicates_only: bool,
+
+        /// Sort chunks by this key (ties are sorted by path)
+        #[arg(long, value_enum, default_value_t = ListSortKey::Path)]
+        sort: ListSortKey,
+
+        /// Reverse the sort order
+        #[arg(short, long)]
+        reverse: bool,
+
+        /// Only list the first N chunks after sorting
+        #[arg(short = 'n', long, value_name = "N")]
+        limit: Option<usize>,
+
+        /// Columns to show in the table and csv formats, in order (comma separated)
+        #[arg(short = 'c', long, value_enum, value_delimiter = ',', num_args = 1..)]
+        columns: Option<Vec<ListColumn>>,
+
         /// Show summary statistics
         #[arg(short = 's', long, default_value_t = true)]
         stats: bool,
     },
+    /// Write the chunk path hashes that no hashtable can resolve to a file
+    ///
+    /// Hashes are deduplicated across all wads and written one per line, sorted,
+    /// optionally followed by the detected file kind and the uncompressed size.
+    #[command(visible_alias = "uh")]
+    UnknownHashes {
+        /// Wad files, glob patterns or directories (searched recursively for wad files)
+        #[arg(short, long, required = true, num_args = 1..)]
+        input: Vec<String>,
+
+        /// Path to the output file
+        #[arg(short, long)]
+        output: String,
+
+        /// Path to the hashtable file
+        #[arg(short = 'H', long, visible_short_alias = 'd')]
+        hashtable: Option<String>,
+
+        /// Also write the file kind of each chunk, detected like `list --detect`
+        #[arg(short, long)]
+        kind: bool,
+
+        /// Also write the uncompressed size of each chunk
+        #[arg(short, long)]
+        size: bool,
+    },
+    /// Discover unknown chunk paths by scanning chunk contents for path strings
+    ///
+    /// Decompresses every chunk, hashes the path-like strings it contains and reports the ones
+    /// matching an unknown chunk, in the `<hash> <path>` hashtable format.
+    #[command(visible_alias = "dh")]
+    DiscoverHashes {
+        /// Wad files, glob patterns or directories (searched recursively for wad files)
+        #[arg(short, long, required = true, num_args = 1..)]
+        input: Vec<String>,
+
+        /// Hashtable file to append the discovered entries to (prints them when not set)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Path to the hashtable file
+        #[arg(short = 'H', long, visible_short_alias = 'd')]
+        hashtable: Option<String>,
+    },
+    /// Guess unknown chunk paths by hashing expanded path templates
+    ///
+    /// Placeholders: `{name}` takes every word of the wordlist named `name`, `{0..99}` every
+    /// number in the range (zero padded like `{00..99}`) and `{a,b,c}` every alternative.
+    #[command(visible_alias = "g")]
+    Guess {
+        /// Wad files, glob patterns or directories whose unknown chunk paths are guessed
+        #[arg(short, long, required = true, num_args = 1..)]
+        input: Vec<String>,
+
+        /// Path template, e.g. `assets/characters/{champ}/skins/skin{0..99}/{name}.tex`
+        #[arg(short, long = "template", value_name = "TEMPLATE", required = true)]
+        templates: Vec<String>,
+
+        /// Wordlist file for a `{NAME}` placeholder, one word per line
+        #[arg(short, long = "wordlist", value_name = "NAME=FILE", value_parser = parse_wordlist)]
+        wordlists: Vec<Wordlist>,
+
+        /// Hashtable file to append the hits to (prints them when not set)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Path to the hashtable file
+        #[arg(short = 'H', long, visible_short_alias = 'd')]
+        hashtable: Option<String>,
+
+        /// Number of worker threads (defaults to the number of available cores)
+        #[arg(short, long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        jobs: Option<u64>,
+    },
     /// Download/update WAD hashtables from CommunityDragon
     ///
-    /// Downloads hashes.game.txt and hashes.lcu.txt to the configured hashtable directory.
+    /// Downloads hashes.game.txt and hashes.lcu.txt to the configured hashtable directory,
+    /// or the `[[hash_sources]]` configured in wadtools.toml.
     #[command(visible_alias = "dl")]
-    DownloadHashes,
+    DownloadHashes {
+        /// Names of the hash sources to download (defaults to all of them)
+        sources: Vec<String>,
+
+        /// List the available hash sources
+        #[arg(long)]
+        list_sources: bool,
+
+        /// Install the hashtables from a local directory, .zip/.tar.gz bundle or a checkout of
+        /// the CommunityDragon data repository instead of downloading them
+        #[arg(long, value_name = "PATH")]
+        from: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HashtableCommands {
+    /// Merge hashtable files into one, sorted by hash
+    ///
+    /// Later inputs win over earlier ones, except that entries whose name hashes to their hash
+    /// are never replaced by entries that don't. Comments and blank lines are not kept.
+    Merge {
+        /// Hashtable files or directories
+        #[arg(short, long, required = true, num_args = 1..)]
+        input: Vec<String>,
+
+        /// Path to the output hashtable file (can be one of the inputs)
+        #[arg(short, long)]
+        output: String,
+
+        /// Kind of hash (detected from the file name by default: `hashes.bin*` are fnv1a)
+        #[arg(short, long, value_enum)]
+        kind: Option<HashKind>,
+
+        /// Drop entries whose name doesn't hash to their hash
+        #[arg(long)]
+        drop_invalid: bool,
+    },
+    /// Remove duplicate entries from a hashtable file, keeping the order of the rest
+    ///
+    /// Comments and blank lines are not kept.
+    Dedupe {
+        /// Path to the hashtable file
+        #[arg(short, long)]
+        input: String,
+
+        /// Path to the output hashtable file (defaults to overwriting the input)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Kind of hash (detected from the file name by default: `hashes.bin*` are fnv1a)
+        #[arg(short, long, value_enum)]
+        kind: Option<HashKind>,
+
+        /// Drop entries whose name doesn't hash to their hash
+        #[arg(long)]
+        drop_invalid: bool,
+    },
+    /// Report hash mismatches, duplicates, conflicts and malformed lines
+    ///
+    /// Fails when any problem is found.
+    Validate {
+        /// Hashtable files or directories (defaults to the hashtable directory)
+        #[arg(short, long, num_args = 1..)]
+        input: Vec<String>,
+
+        /// Kind of hash (detected from the file name by default: `hashes.bin*` are fnv1a)
+        #[arg(short, long, value_enum)]
+        kind: Option<HashKind>,
+    },
+    /// Print the entries added, removed and changed between two hashtable files
+    Diff {
+        /// Path to the reference hashtable file
+        #[arg(short, long)]
+        reference: String,
+
+        /// Path to the target hashtable file
+        #[arg(short, long)]
+        target: String,
+
+        /// Kind of hash (detected from the file name by default: `hashes.bin*` are fnv1a)
+        #[arg(short, long, value_enum)]
+        kind: Option<HashKind>,
+    },
+    /// Print entry, duplicate, conflict and mismatch counts per hashtable file
+    Stats {
+        /// Hashtable files or directories (defaults to the hashtable directory)
+        #[arg(short, long, num_args = 1..)]
+        input: Vec<String>,
+
+        /// Kind of hash (detected from the file name by default: `hashes.bin*` are fnv1a)
+        #[arg(short, long, value_enum)]
+        kind: Option<HashKind>,
+    },
 }
 
 fn main() -> eyre::Result<()> {
@@ -230,12 +552,18 @@ fn main() -> eyre::Result<()> {
             filter_type,
             pattern,
             list_filters,
+            jobs,
+            merge,
+            on_conflict,
+            incremental,
         } => {
             if list_filters {
                 print_supported_filters();
                 return Ok(());
             }
-            let input = input.ok_or_else(|| eyre::eyre!("Input file is required"))?;
+            if input.is_empty() {
+                return Err(eyre::eyre!("Input file is required"));
+            }
             extract(ExtractArgs {
                 input,
                 output,
@@ -243,20 +571,126 @@ fn main() -> eyre::Result<()> {
                 filter_type,
                 pattern,
                 hashtable_dir: args.hashtable_dir.or_else(|| config.hashtable_dir.clone()),
+                strict_hashtables: args.strict_hashtables,
+                jobs: jobs.map(|jobs| jobs as usize),
+                merge,
+                on_conflict,
+                incremental,
             })
         }
+        Commands::Pack {
+            input,
+            output,
+            compression,
+        } => pack(PackArgs {
+            input,
+            output,
+            compression,
+        }),
+        Commands::Edit {
+            input,
+            output,
+            add,
+            replace,
+            remove,
+            compression,
+            hashtable,
+        } => edit(EditArgs {
+            input,
+            output,
+            add,
+            replace,
+            remove,
+            compression,
+            hashtable,
+            hashtable_dir: args.hashtable_dir.or_else(|| config.hashtable_dir.clone()),
+            strict_hashtables: args.strict_hashtables,
+        }),
         Commands::Diff {
             reference,
             target,
             hashtable,
             output,
+            format,
+            content,
+            bin_hashtable,
         } => diff(DiffArgs {
             reference,
             target,
             hashtable_path: hashtable,
             output,
             hashtable_dir: args.hashtable_dir.or_else(|| config.hashtable_dir.clone()),
+            strict_hashtables: args.strict_hashtables,
+            content,
+            bin_hashtables: bin_hashtable,
+            format,
+        }),
+        Commands::Hash {
+            values,
+            kind,
+            reverse,
+            hashtable,
+        } => hash(HashArgs {
+            values,
+            kind,
+            reverse,
+            hashtable,
+            hashtable_dir: args.hashtable_dir.or_else(|| config.hashtable_dir.clone()),
+            strict_hashtables: args.strict_hashtables,
         }),
+        Commands::Hashtable { command } => {
+            let hashtable_dir = args.hashtable_dir.or_else(|| config.hashtable_dir.clone());
+            match command {
+                HashtableCommands::Merge {
+                    input,
+                    output,
+                    kind,
+                    drop_invalid,
+                } => hashtable_merge(HashtableMergeArgs {
+                    input,
+                    output,
+                    kind,
+                    drop_invalid,
+                    strict_hashtables: args.strict_hashtables,
+                }),
+                HashtableCommands::Dedupe {
+                    input,
+                    output,
+                    kind,
+                    drop_invalid,
+                } => hashtable_dedupe(HashtableDedupeArgs {
+                    input,
+                    output,
+                    kind,
+                    drop_invalid,
+                    strict_hashtables: args.strict_hashtables,
+                }),
+                HashtableCommands::Validate { input, kind } => {
+                    hashtable_validate(HashtableValidateArgs {
+                        input,
+                        kind,
+                        hashtable_dir,
+                        strict_hashtables: args.strict_hashtables,
+                    })
+                }
+                HashtableCommands::Diff {
+                    reference,
+                    target,
+                    kind,
+                } => hashtable_diff(HashtableDiffArgs {
+                    reference,
+                    target,
+                    kind,
+                    strict_hashtables: args.strict_hashtables,
+                }),
+                HashtableCommands::Stats { input, kind } => hashtable_stats(HashtableStatsArgs {
+                    input,
+                    kind,
+                    hashtable_dir,
+                    strict_hashtables: args.strict_hashtables,
+                }),
+            }
+        }
         Commands::HashtableDir => {
             if let Some(dir) = default_hashtable_dir() {
                 println!("{}", dir);
@@ -271,18 +705,86 @@ fn main() -> eyre::Result<()> {
             filter_type,
             pattern,
             format,
+            depth,
+            detect,
+            compression,
+            duplicates_only,
+            sort,
+            reverse,
+            limit,
+            columns,
             stats,
         } => list(ListArgs {
             input,
             hashtable,
             hashtable_dir: args.hashtable_dir.or_else(|| config.hashtable_dir.clone()),
+            strict_hashtables: args.strict_hashtables,
             filter_type,
             pattern,
             format,
+            depth,
+            detect,
+            compression,
+            duplicates_only,
+            sort,
+            reverse,
+            limit,
+            columns,
             show_stats: stats,
         }),
-        Commands::DownloadHashes => download_hashes(DownloadHashesArgs {
+        Commands::UnknownHashes {
+            input,
+            output,
+            hashtable,
+            kind,
+            size,
+        } => unknown_hashes(UnknownHashesArgs {
+            input,
+            output,
+            hashtable,
+            hashtable_dir: args.hashtable_dir.or_else(|| config.hashtable_dir.clone()),
+            strict_hashtables: args.strict_hashtables,
+            kind,
+            size,
+        }),
+        Commands::DiscoverHashes {
+            input,
+            output,
+            hashtable,
+        } => discover_hashes(DiscoverHashesArgs {
+            input,
+            output,
+            hashtable,
+            hashtable_dir: args.hashtable_dir.or_else(|| config.hashtable_dir.clone()),
+            strict_hashtables: args.strict_hashtables,
+        }),
+        Commands::Guess {
+            input,
+            templates,
+            wordlists,
+            output,
+            hashtable,
+            jobs,
+        } => guess(GuessArgs {
+            input,
+            templates,
+            wordlists,
+            output,
+            hashtable,
+            hashtable_dir: args.hashtable_dir.or_else(|| config.hashtable_dir.clone()),
+            strict_hashtables: args.strict_hashtables,
+            jobs: jobs.map(|jobs| jobs as usize),
+        }),
+        Commands::DownloadHashes {
+            sources,
+            list_sources,
+            from,
+        } => download_hashes(DownloadHashesArgs {
             hashtable_dir: args.hashtable_dir.or_else(|| config.hashtable_dir.clone()),
+            hash_sources: config.hash_sources.clone(),
+            sources,
+            list_sources,
+            from,
         }),
     }
 }
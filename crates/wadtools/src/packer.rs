@@ -0,0 +1,373 @@
+use crate::{
+    extract_manifest::EXTRACT_MANIFEST_FILE_NAME,
+    utils::{hash_chunk_path, truncate_middle, WadWriter},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{self, eyre};
+use eyre::Context;
+use league_toolkit::{
+    file::LeagueFileKind,
+    wad::{FileExt as _, WadChunkCompression},
+};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Seek, Write},
+};
+use tracing_indicatif::span_ext::IndicatifSpanExt;
+use tracing_indicatif::style::ProgressStyle;
+use walkdir::WalkDir;
+
+const MAX_LOG_PATH_LEN: usize = 120;
+
+/// A file on disk that will be packed into a wad chunk
+#[derive(Debug, Clone)]
+pub struct PackEntry {
+    pub path_hash: u64,
+    /// Path of the file relative to the packed directory
+    pub relative_path: Utf8PathBuf,
+    pub file_path: Utf8PathBuf,
+}
+
+pub struct Packer {
+    compression: Option<WadChunkCompression>,
+}
+
+impl Packer {
+    /// Creates a packer. If `compression` is `None`, the ideal compression for each chunk's
+    /// detected file kind is used.
+    pub fn new(compression: Option<WadChunkCompression>) -> Self {
+        Self { compression }
+    }
+
+    pub fn pack_entries<W: Write + Seek>(
+        &self,
+        entries: &[PackEntry],
+        writer: W,
+    ) -> eyre::Result<usize> {
+        let total = entries.len() as u64;
+        let span = tracing::info_span!("pack", total = total);
+        let _entered = span.enter();
+        span.pb_set_style(
+            &ProgressStyle::with_template("{wide_bar:40.cyan/blue} {pos}/{len} \n {spinner} {msg}")
+                .unwrap(),
+        );
+        span.pb_set_length(total);
+        span.pb_set_message("Packing chunks");
+        span.pb_set_finish_message("Packing complete");
+
+        pack_wad_chunks(
+            WadWriter::new(writer, entries.len())?,
+            entries,
+            self.compression,
+            |position, message| {
+                span.pb_set_position(position as u64);
+                if let Some(msg) = message {
+                    span.pb_set_message(msg);
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+pub fn pack_wad_chunks<W: Write + Seek>(
+    mut writer: WadWriter<W>,
+    entries: &[PackEntry],
+    compression: Option<WadChunkCompression>,
+    report_progress: impl Fn(usize, Option<&str>) -> eyre::Result<()>,
+) -> eyre::Result<usize> {
+    for (i, entry) in entries.iter().enumerate() {
+        let truncated = truncate_middle(entry.relative_path.as_str(), MAX_LOG_PATH_LEN);
+        report_progress(i, Some(truncated.as_str()))?;
+
+        let data = fs::read(entry.file_path.as_std_path())
+            .wrap_err(format!("failed to read file (path: {})", entry.file_path))?;
+        let compression = compression
+            .unwrap_or_else(|| LeagueFileKind::identify_from_bytes(&data).ideal_compression());
+
+        writer
+            .write_chunk(entry.path_hash, &data, compression)
+            .wrap_err(format!(
+                "failed to write chunk (path: {})",
+                entry.relative_path
+            ))?;
+    }
+    report_progress(entries.len(), None)?;
+
+    writer.finish()?;
+
+    Ok(entries.len())
+}
+
+/// Walks `input_directory` and resolves the chunk path hash of every file in it.
+///
+/// This is the inverse of `extract_wad_chunks`, so the naming conventions it produces
+/// (hashed filenames, `.ltk` suffixes) are mapped back to their original chunk paths.
+pub fn collect_pack_entries(input_directory: impl AsRef<Utf8Path>) -> eyre::Result<Vec<PackEntry>> {
+    let input_directory = input_directory.as_ref();
+    let mut entries = Vec::new();
+    let mut seen_hashes = HashMap::<u64, Utf8PathBuf>::new();
+
+    for entry in WalkDir::new(input_directory.as_std_path()).sort_by_file_name() {
+        let entry = entry?;
+        // Skip the bookkeeping of `extract --incremental`, it is not part of the wad
+        if !entry.file_type().is_file() || entry.file_name() == EXTRACT_MANIFEST_FILE_NAME {
+            continue;
+        }
+
+        let file_path = Utf8PathBuf::from_path_buf(entry.into_path())
+            .map_err(|path| eyre!("non UTF-8 path: {}", path.display()))?;
+        let relative_path = file_path.strip_prefix(input_directory)?.to_path_buf();
+        let path_hash = resolve_pack_chunk_path_hash(input_directory, &relative_path);
+
+        if let Some(existing) = seen_hashes.insert(path_hash, relative_path.clone()) {
+            return Err(eyre!(
+                "multiple files map to the same chunk (path_hash: {:016x}, files: {}, {})",
+                path_hash,
+                existing,
+                relative_path
+            ));
+        }
+
+        entries.push(PackEntry {
+            path_hash,
+            relative_path,
+            file_path,
+        });
+    }
+
+    Ok(entries)
+}
+
+fn resolve_pack_chunk_path_hash(input_directory: &Utf8Path, relative_path: &Utf8Path) -> u64 {
+    let file_name = relative_path.file_name().unwrap_or("");
+    let parent = relative_path.parent().unwrap_or(Utf8Path::new(""));
+
+    // Unresolved and long-filename chunks are extracted as `<16 hex>[.ext]` into the root directory
+    if parent.as_str().is_empty() {
+        if let Some(path_hash) = parse_hex_chunk_file_name(file_name) {
+            return path_hash;
+        }
+    }
+
+    // `extract` keeps the whole original file name in front of `.ltk`
+    if let Some((stem, ext)) = split_ltk_file_name(file_name) {
+        let chunk_path = parent.join(stem);
+
+        // Older versions dropped the extension of paths that collided with a directory, so a
+        // `foo.ltk.bin` next to a `foo.bin` directory may also have been `foo.bin`
+        if let Some(ext) = ext {
+            let with_extension = parent.join(format!("{}.{}", stem, ext));
+            if input_directory.join(&with_extension).is_dir() {
+                tracing::warn!(
+                    "ambiguous .ltk file name, packing it as {} (path: {}, possibly also: {})",
+                    chunk_path,
+                    relative_path,
+                    with_extension
+                );
+            }
+        }
+
+        return hash_chunk_path(chunk_path.as_str());
+    }
+
+    hash_chunk_path(relative_path.as_str())
+}
+
+/// Parses the path hash of `<16 hex>[.ext]` file names
+pub fn parse_hex_chunk_file_name(file_name: &str) -> Option<u64> {
+    let base = file_name
+        .split_once('.')
+        .map_or(file_name, |(base, _)| base);
+    if base.len() != 16 || !base.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u64::from_str_radix(base, 16).ok()
+}
+
+/// Splits `<stem>.ltk` and `<stem>.ltk.<ext>` file names into their stem and extension
+pub fn split_ltk_file_name(file_name: &str) -> Option<(&str, Option<&str>)> {
+    if let Some(stem) = file_name.strip_suffix(".ltk") {
+        return Some((stem, None));
+    }
+
+    let (before, ext) = file_name.rsplit_once('.')?;
+    let stem = before.strip_suffix(".ltk")?;
+    Some((stem, Some(ext)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{pack, PackArgs, PackCompression};
+    use crate::extractor::{extract_wad_chunks, ChunkDirectories};
+    use crate::utils::{PartialFile, WadHashtable};
+    use league_toolkit::wad::Wad;
+    use std::{collections::BTreeSet, fs::File, io::BufWriter, sync::Arc};
+    use xxhash_rust::xxh3::xxh3_64;
+
+    fn write_file(dir: &Utf8Path, relative_path: &str, contents: &[u8]) {
+        let path = dir.join(relative_path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn pack_round_trips_extracted_naming_conventions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(temp_dir.path()).unwrap();
+        let input_dir = root.join("Test.wad");
+
+        // (file as extracted, chunk path hash, contents)
+        let files = [
+            (
+                "data/characters/test/test.bin",
+                hash_chunk_path("data/characters/test/test.bin"),
+                b"PROP plain path".to_vec(),
+            ),
+            (
+                "0123456789abcdef.dds",
+                0x0123456789abcdef,
+                b"DDS unresolved path".to_vec(),
+            ),
+            (
+                "assets/no_extension.ltk",
+                hash_chunk_path("assets/no_extension"),
+                b"no extension".to_vec(),
+            ),
+            (
+                "assets/detected.ltk.png",
+                hash_chunk_path("assets/detected"),
+                b"\x89PNG\r\n\x1a\n detected extension".to_vec(),
+            ),
+            // `assets/collision.bin` is both a chunk and a directory
+            (
+                "assets/collision.bin.ltk",
+                hash_chunk_path("assets/collision.bin"),
+                b"collides with a directory".to_vec(),
+            ),
+            (
+                "assets/collision.bin/child.txt",
+                hash_chunk_path("assets/collision.bin/child.txt"),
+                vec![b'x'; 64 * 1024],
+            ),
+        ];
+        for (relative_path, _, contents) in &files {
+            write_file(&input_dir, relative_path, contents);
+        }
+
+        let output_path = root.join("Test.wad.client");
+        pack(PackArgs {
+            input: input_dir.to_string(),
+            output: Some(output_path.to_string()),
+            compression: PackCompression::Auto,
+        })
+        .unwrap();
+
+        let source = File::open(output_path.as_std_path()).unwrap();
+        let mut wad = Wad::mount(&source).unwrap();
+        let (mut decoder, chunks) = wad.decode();
+        assert_eq!(chunks.len(), files.len());
+
+        for (relative_path, path_hash, contents) in &files {
+            let chunk = chunks
+                .get(path_hash)
+                .unwrap_or_else(|| panic!("missing chunk for {}", relative_path));
+            let raw_data = decoder.load_chunk_raw(chunk).unwrap();
+            assert_eq!(chunk.checksum, xxh3_64(&raw_data), "{}", relative_path);
+            let data = decoder.load_chunk_decompressed(chunk).unwrap();
+            assert_eq!(&data[..], &contents[..], "{}", relative_path);
+        }
+
+        // Nothing is left next to the output
+        assert_eq!(fs::read_dir(root.as_std_path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn extract_then_pack_restores_ambiguous_ltk_paths() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(temp_dir.path()).unwrap();
+
+        let png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let chunk_paths = [
+            // No extension, with a detected kind
+            ("assets/no_extension", png.clone()),
+            // Collides with a directory and has an extension that differs from its kind
+            ("assets/collision.bin", png.clone()),
+            ("assets/collision.bin/child.txt", b"child".to_vec()),
+            // Collides with a directory and has a kind matching a sibling directory
+            ("assets/other", png.clone()),
+            ("assets/other.png/child.txt", b"child".to_vec()),
+            // Names that already look like `.ltk` names
+            ("assets/literal.ltk", b"literal".to_vec()),
+            ("assets/literal.ltk.png", png.clone()),
+        ];
+
+        let wad_path = root.join("Test.wad.client");
+        let mut hashtable = WadHashtable::new().unwrap();
+        let mut writer = WadWriter::new(
+            BufWriter::new(File::create(&wad_path).unwrap()),
+            chunk_paths.len(),
+        )
+        .unwrap();
+        for (chunk_path, data) in &chunk_paths {
+            let path_hash = hash_chunk_path(chunk_path);
+            hashtable
+                .items_mut()
+                .insert(path_hash, Arc::from(*chunk_path));
+            writer
+                .write_chunk(path_hash, data, WadChunkCompression::None)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let source = File::open(wad_path.as_std_path()).unwrap();
+        let mut wad = Wad::mount(&source).unwrap();
+        let (_, chunks) = wad.decode();
+        let extract_dir = root.join("Test.wad");
+        extract_wad_chunks(
+            &wad_path,
+            chunks,
+            &ChunkDirectories::collect(chunks, &hashtable),
+            &hashtable,
+            extract_dir.clone(),
+            |_| Ok(()),
+            None,
+            None,
+            1,
+        )
+        .unwrap();
+
+        let packed_hashes = collect_pack_entries(&extract_dir)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path_hash)
+            .collect::<BTreeSet<_>>();
+        let chunk_hashes = chunks.keys().copied().collect::<BTreeSet<_>>();
+        assert_eq!(packed_hashes, chunk_hashes);
+    }
+
+    #[test]
+    fn failed_pack_keeps_previous_output() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(temp_dir.path()).unwrap();
+        let input_dir = root.join("Test.wad");
+        write_file(&input_dir, "a.bin", b"a");
+        write_file(&input_dir, "b.bin", b"b");
+        let output_path = root.join("Test.wad.client");
+        fs::write(&output_path, b"previous wad").unwrap();
+
+        // A source file that disappears while packing
+        let entries = collect_pack_entries(&input_dir).unwrap();
+        fs::remove_file(input_dir.join("b.bin")).unwrap();
+        let mut output_file = PartialFile::create(&output_path).unwrap();
+        let result = Packer::new(None).pack_entries(&entries, BufWriter::new(output_file.file()));
+        assert!(result.is_err());
+        drop(output_file);
+
+        assert_eq!(fs::read(&output_path).unwrap(), b"previous wad");
+        assert_eq!(fs::read_dir(root.as_std_path()).unwrap().count(), 2);
+    }
+}
@@ -0,0 +1,90 @@
+use camino::Utf8Path;
+use color_eyre::eyre::{self, Result};
+use std::{collections::HashMap, path::Path, sync::Arc};
+use tracing::info;
+use walkdir::WalkDir;
+
+use super::{for_each_hashtable_entry, is_hashtable_bookkeeping_file, is_text_file};
+
+/// Number of hex digits in a bin hash
+const BIN_HASH_DIGITS: usize = 8;
+
+/// Resolves the 32-bit FNV-1a hashes used inside property bins (entry paths, class names,
+/// field names and hash values), as found in `hashes.binentries.txt`, `hashes.binfields.txt`,
+/// `hashes.bintypes.txt` and `hashes.binhashes.txt`.
+#[derive(Debug, Clone, Default)]
+pub struct BinHashtable {
+    items: HashMap<u32, Arc<str>>,
+    /// Whether malformed lines fail loading instead of being skipped
+    strict: bool,
+}
+
+impl BinHashtable {
+    pub fn new() -> Result<Self> {
+        Ok(BinHashtable {
+            items: HashMap::default(),
+            strict: false,
+        })
+    }
+
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn resolve(&self, hash: u32) -> Arc<str> {
+        self.items
+            .get(&hash)
+            .cloned()
+            .unwrap_or_else(|| format!("{:08x}", hash).into())
+    }
+
+    /// Whether the hash can be resolved to a name
+    pub fn contains(&self, hash: u32) -> bool {
+        self.items.contains_key(&hash)
+    }
+
+    /// Loads all bin hashtable files (`hashes.bin*`) from the directory
+    pub fn add_from_dir(&mut self, dir: impl AsRef<Utf8Path>) -> eyre::Result<()> {
+        for entry in WalkDir::new(dir.as_ref().as_std_path())
+            .into_iter()
+            .filter_map(|x| x.ok())
+        {
+            if !entry.file_type().is_file()
+                || !is_bin_hashtable_file(entry.path())
+                || is_hashtable_bookkeeping_file(entry.path())
+            {
+                continue;
+            }
+            let Some(path) = Utf8Path::from_path(entry.path()) else {
+                tracing::warn!("skipping hashtable with non UTF-8 path: {:?}", entry.path());
+                continue;
+            };
+            if !is_text_file(path)? {
+                tracing::debug!("skipping non-text file in hashtable directory: {}", path);
+                continue;
+            }
+
+            info!("loading bin hashtable: {:?}", entry.path());
+            self.add_from_file(path)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn add_from_file(&mut self, path: impl AsRef<Utf8Path>) -> eyre::Result<()> {
+        for_each_hashtable_entry(path.as_ref(), BIN_HASH_DIGITS, self.strict, |hash, name| {
+            // At most 8 hex digits, so the hash always fits
+            self.items.insert(hash as u32, name.into());
+        })?;
+
+        Ok(())
+    }
+}
+
+/// Whether the file is a property bin hashtable rather than a wad hashtable
+pub fn is_bin_hashtable_file(path: impl AsRef<Path>) -> bool {
+    path.as_ref()
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with("hashes.bin"))
+}
@@ -1,5 +1,5 @@
 use camino::{Utf8Path, Utf8PathBuf};
-use eyre::Result;
+use eyre::{eyre, Result};
 use serde::{Deserialize, Serialize};
 use std::fs;
 
@@ -9,6 +9,65 @@ pub struct Config {
     pub show_progress: Option<bool>,
     /// Optional custom directory to recursively load hashtable files from
     pub hashtable_dir: Option<String>,
+    /// Hashtables downloaded by `download-hashes`, replacing the CommunityDragon ones when set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hash_sources: Vec<HashSource>,
+}
+
+/// A hashtable file to download, configured as a `[[hash_sources]]` entry
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HashSource {
+    /// Name used to select the source, e.g. `wadtools download-hashes game`
+    pub name: String,
+    pub url: String,
+    /// File name in the hashtable directory, defaults to the last segment of the url
+    pub filename: Option<String>,
+}
+
+impl HashSource {
+    fn community_dragon(name: &str, filename: &str) -> Self {
+        HashSource {
+            name: name.to_string(),
+            url: format!(
+                "https://raw.communitydragon.org/binviewer/hashes/{}",
+                filename
+            ),
+            filename: None,
+        }
+    }
+
+    /// The file name the source is saved as, never a path so it stays inside the hashtable
+    /// directory
+    pub fn filename(&self) -> Result<&str> {
+        let filename = match &self.filename {
+            Some(filename) => filename.as_str(),
+            None => {
+                let path = self.url.split(['?', '#']).next().unwrap_or_default();
+                path.rsplit('/').next().unwrap_or_default()
+            }
+        };
+
+        if filename.is_empty()
+            || filename == "."
+            || filename == ".."
+            || filename.contains(['/', '\\'])
+        {
+            return Err(eyre!(
+                "invalid file name for hash source `{}`: `{}` (set `filename`)",
+                self.name,
+                filename
+            ));
+        }
+        Ok(filename)
+    }
+}
+
+/// The CommunityDragon hashtables downloaded when no `[[hash_sources]]` are configured
+pub fn default_hash_sources() -> Vec<HashSource> {
+    vec![
+        HashSource::community_dragon("game", "hashes.game.txt"),
+        HashSource::community_dragon("lcu", "hashes.lcu.txt"),
+    ]
 }
 
 impl Config {
@@ -1,69 +1,178 @@
 use camino::Utf8Path;
-use color_eyre::eyre::{self, eyre, Result};
+use color_eyre::eyre::{self, eyre, Context, Result};
+use league_toolkit::wad::Wad;
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::{BufRead, BufReader},
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
     sync::Arc,
 };
 use tracing::info;
 use walkdir::WalkDir;
 
-use super::format_chunk_path_hash;
+use super::{
+    format_chunk_path_hash, is_bin_hashtable_file, is_hashtable_cache_file, is_partial_file,
+    HashtableCache,
+};
+
+/// Number of hex digits in a wad path hash
+pub const WAD_HASH_DIGITS: usize = 16;
+
+/// Suffix of the files storing the ETag and Last-Modified of a downloaded hashtable
+pub const DOWNLOAD_METADATA_SUFFIX: &str = ".wadtools-meta";
+
+/// Number of malformed lines reported per hashtable file, the rest are only counted
+const MAX_REPORTED_MALFORMED_LINES: usize = 10;
 
 #[derive(Debug, Clone, Default)]
 pub struct WadHashtable {
     items: HashMap<u64, Arc<str>>,
+    /// Binary caches of the text hashtables loaded from a directory, in load order
+    caches: Vec<Arc<HashtableCache>>,
+    /// When set, only entries for these hashes are kept
+    filter: Option<Arc<HashSet<u64>>>,
+    /// Whether malformed lines fail loading instead of being skipped
+    strict: bool,
 }
 
 impl WadHashtable {
     pub fn new() -> Result<Self> {
         Ok(WadHashtable {
             items: HashMap::default(),
+            caches: Vec::new(),
+            filter: None,
+            strict: false,
+        })
+    }
+
+    /// Creates a hashtable that only keeps the entries for the given path hashes, so resolving
+    /// the chunks of a few wads doesn't require loading every known path into memory
+    pub fn with_path_hashes(path_hashes: impl IntoIterator<Item = u64>) -> Result<Self> {
+        Ok(WadHashtable {
+            filter: Some(Arc::new(path_hashes.into_iter().collect())),
+            ..Self::new()?
         })
     }
 
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Resolves a path hash, preferring entries added from files over cached ones and later
+    /// caches over earlier ones
     pub fn resolve_path(&self, path_hash: u64) -> Arc<str> {
-        self.items
-            .get(&path_hash)
-            .cloned()
+        if let Some(path) = self.items.get(&path_hash) {
+            return path.clone();
+        }
+
+        self.caches
+            .iter()
+            .rev()
+            .find_map(|cache| cache.resolve(path_hash))
+            .map(Arc::from)
             .unwrap_or_else(|| format_chunk_path_hash(path_hash).into())
     }
 
+    /// Whether the path hash can be resolved to a path
+    pub fn contains(&self, path_hash: u64) -> bool {
+        self.items.contains_key(&path_hash)
+            || self
+                .caches
+                .iter()
+                .any(|cache| cache.resolve(path_hash).is_some())
+    }
+
     #[allow(dead_code)]
     pub fn add_from_dir(&mut self, dir: impl AsRef<Utf8Path>) -> eyre::Result<()> {
         for wad_hashtable_entry in WalkDir::new(dir.as_ref().as_std_path())
             .into_iter()
             .filter_map(|x| x.ok())
         {
-            if !wad_hashtable_entry.file_type().is_file() {
+            // Property bin hashtables share the directory but map to 32-bit hashes
+            if !wad_hashtable_entry.file_type().is_file()
+                || is_bin_hashtable_file(wad_hashtable_entry.path())
+                || is_hashtable_bookkeeping_file(wad_hashtable_entry.path())
+            {
+                continue;
+            }
+            let Some(path) = Utf8Path::from_path(wad_hashtable_entry.path()) else {
+                tracing::warn!(
+                    "skipping hashtable with non UTF-8 path: {:?}",
+                    wad_hashtable_entry.path()
+                );
+                continue;
+            };
+            if !is_text_file(path)? {
+                tracing::debug!("skipping non-text file in hashtable directory: {}", path);
                 continue;
             }
 
             info!("loading wad hasthable: {:?}", wad_hashtable_entry.path());
-            self.add_from_file(&File::open(wad_hashtable_entry.path())?)?;
+            if let Some(filter) = self.filter.clone() {
+                self.add_filtered_from_path(path, &filter)?;
+                continue;
+            }
+
+            match HashtableCache::open_or_build(path, self.strict) {
+                Ok(cache) => self.caches.push(Arc::new(cache)),
+                Err(error) => {
+                    // The directory might be read-only, fall back to parsing the text file
+                    tracing::debug!("hashtable cache unavailable for {} ({})", path, error);
+                    self.add_from_file(path)?;
+                }
+            }
         }
 
         Ok(())
     }
 
-    pub fn add_from_file(&mut self, file: &File) -> eyre::Result<()> {
-        let reader = BufReader::new(file);
-        let mut lines = reader.lines();
-
-        while let Some(Ok(line)) = lines.next() {
-            let mut components = line.split(' ');
-
-            let hash = components.next().ok_or(eyre!("failed to read hash"))?;
-            let hash = u64::from_str_radix(hash, 16).expect("failed to convert hash");
-            let path = itertools::join(components, " ");
-
-            self.items.insert(hash, path.into());
+    pub fn add_from_file(&mut self, path: impl AsRef<Utf8Path>) -> eyre::Result<()> {
+        let filter = self.filter.clone();
+        let mut override_count = 0;
+        for_each_hashtable_entry(path.as_ref(), WAD_HASH_DIGITS, self.strict, |hash, path| {
+            if filter.as_ref().is_none_or(|filter| filter.contains(&hash)) {
+                if let Some(previous) = self.items.insert(hash, path.into()) {
+                    override_count += usize::from(*previous != *path);
+                }
+            }
+        })?;
+        // Later files win, which hides conflicting entries
+        if override_count > 0 {
+            tracing::debug!(
+                "{} entries of {} override a different path (see `hashtable validate`)",
+                override_count,
+                path.as_ref()
+            );
         }
 
         Ok(())
     }
 
+    /// Copies the wanted entries out of an up-to-date cache if there is one, otherwise streams
+    /// the text file. Never builds a cache, since that requires reading every entry into memory.
+    fn add_filtered_from_path(
+        &mut self,
+        path: &Utf8Path,
+        filter: &HashSet<u64>,
+    ) -> eyre::Result<()> {
+        match HashtableCache::open_if_fresh(path, self.strict) {
+            Ok(Some(cache)) => {
+                for hash in filter {
+                    if let Some(resolved) = cache.resolve(*hash) {
+                        self.items.insert(*hash, resolved.into());
+                    }
+                }
+                Ok(())
+            }
+            Ok(None) => self.add_from_file(path),
+            Err(error) => {
+                tracing::debug!("hashtable cache unavailable for {} ({})", path, error);
+                self.add_from_file(path)
+            }
+        }
+    }
+
     #[allow(dead_code)]
     pub fn items(&self) -> &HashMap<u64, Arc<str>> {
         &self.items
@@ -73,3 +182,199 @@ impl WadHashtable {
         &mut self.items
     }
 }
+
+/// Collects the path hashes of all chunks in the given wads
+pub fn collect_wad_path_hashes<'a>(
+    wad_paths: impl IntoIterator<Item = &'a Utf8Path>,
+) -> eyre::Result<HashSet<u64>> {
+    let mut path_hashes = HashSet::new();
+    for wad_path in wad_paths {
+        let file = File::open(wad_path).wrap_err(format!("failed to open wad: {}", wad_path))?;
+        let wad = Wad::mount(&file).wrap_err(format!("failed to mount wad: {}", wad_path))?;
+        path_hashes.extend(wad.chunks().keys().copied());
+    }
+
+    Ok(path_hashes)
+}
+
+/// Appends entries to a hashtable file, creating it if needed
+pub fn append_hashtable_entries(
+    path: &Utf8Path,
+    entries: &BTreeMap<u64, String>,
+) -> eyre::Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    // Don't glue the first entry onto a last line without a line break
+    let needs_line_break = match File::open(path.as_std_path()) {
+        Ok(mut file) if file.metadata()?.len() > 0 => {
+            let mut last_byte = [0u8];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last_byte)?;
+            last_byte[0] != b'\n'
+        }
+        _ => false,
+    };
+
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path.as_std_path())
+        .wrap_err(format!("failed to open hashtable: {}", path))?;
+    let mut writer = BufWriter::new(file);
+    if needs_line_break {
+        writeln!(writer)?;
+    }
+    for (path_hash, chunk_path) in entries {
+        writeln!(
+            writer,
+            "{} {}",
+            format_chunk_path_hash(*path_hash),
+            chunk_path
+        )?;
+    }
+    writer.flush()?;
+
+    tracing::info!("appended {} entries to {}", entries.len(), path);
+    Ok(())
+}
+
+/// Reads the entries of a wad hashtable in file order, along with the number of skipped
+/// malformed lines
+pub fn read_hashtable_entries(
+    path: &Utf8Path,
+    strict: bool,
+) -> eyre::Result<(Vec<(u64, String)>, usize)> {
+    let mut entries = Vec::new();
+    let malformed_count = for_each_hashtable_entry(path, WAD_HASH_DIGITS, strict, |hash, path| {
+        entries.push((hash, path.to_string()))
+    })?;
+
+    Ok((entries, malformed_count))
+}
+
+/// Streams the `<hex hash> <name>` entries of a text hashtable line by line, in file order.
+///
+/// Blank lines and lines starting with `#` are ignored, and both LF and CRLF line endings are
+/// accepted. Malformed lines fail with their file and line number when `strict` is set,
+/// otherwise they are reported and skipped. Returns the number of skipped lines.
+pub fn for_each_hashtable_entry(
+    path: &Utf8Path,
+    hash_digits: usize,
+    strict: bool,
+    mut on_entry: impl FnMut(u64, &str),
+) -> eyre::Result<usize> {
+    for_each_hashtable_line(path, hash_digits, strict, |_, hash, name| {
+        on_entry(hash, name)
+    })
+}
+
+/// Like [`for_each_hashtable_entry`], also passing the 1-based line number of every entry
+pub fn for_each_hashtable_line(
+    path: &Utf8Path,
+    hash_digits: usize,
+    strict: bool,
+    mut on_entry: impl FnMut(usize, u64, &str),
+) -> eyre::Result<usize> {
+    let file = File::open(path).wrap_err(format!("failed to open hashtable: {}", path))?;
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
+    let mut line_number = 0;
+    let mut malformed_count = 0;
+
+    loop {
+        line.clear();
+        let read = reader
+            .read_until(b'\n', &mut line)
+            .wrap_err(format!("failed to read hashtable: {}", path))?;
+        if read == 0 {
+            break;
+        }
+        line_number += 1;
+
+        match parse_hashtable_line(&line, hash_digits) {
+            Ok(Some((hash, name))) => on_entry(line_number, hash, name),
+            Ok(None) => {}
+            Err(error) if strict => {
+                return Err(error.wrap_err(format!(
+                    "malformed hashtable line ({}:{})",
+                    path, line_number
+                )));
+            }
+            Err(error) => {
+                malformed_count += 1;
+                if malformed_count <= MAX_REPORTED_MALFORMED_LINES {
+                    tracing::warn!(
+                        "skipping malformed hashtable line ({}:{}): {}",
+                        path,
+                        line_number,
+                        error
+                    );
+                }
+            }
+        }
+    }
+
+    if malformed_count > MAX_REPORTED_MALFORMED_LINES {
+        tracing::warn!(
+            "skipped {} malformed lines in {} (use --strict-hashtables to fail on them)",
+            malformed_count,
+            path
+        );
+    }
+
+    Ok(malformed_count)
+}
+
+/// Parses a single hashtable line, returns `None` for blank lines and comments
+fn parse_hashtable_line(line: &[u8], hash_digits: usize) -> eyre::Result<Option<(u64, &str)>> {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let line = std::str::from_utf8(line).map_err(|_| eyre!("line is not valid UTF-8"))?;
+
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return Ok(None);
+    }
+
+    let (hash, name) = line
+        .split_once(' ')
+        .ok_or_else(|| eyre!("expected `<hash> <name>`, got: {}", line))?;
+    if hash.is_empty()
+        || hash.len() > hash_digits
+        || !hash.bytes().all(|byte| byte.is_ascii_hexdigit())
+    {
+        return Err(eyre!("invalid hash: {}", hash));
+    }
+    if name.is_empty() {
+        return Err(eyre!("missing name for hash: {}", hash));
+    }
+
+    let hash = u64::from_str_radix(hash, 16)?;
+    Ok(Some((hash, name)))
+}
+
+/// Whether the file looks like text. Hashtable directories can contain unrelated files like
+/// archives, which are skipped instead of being reported line by line.
+pub fn is_text_file(path: &Utf8Path) -> eyre::Result<bool> {
+    let file = File::open(path).wrap_err(format!("failed to open file: {}", path))?;
+    let mut head = Vec::with_capacity(8192);
+    file.take(8192)
+        .read_to_end(&mut head)
+        .wrap_err(format!("failed to read file: {}", path))?;
+
+    Ok(!head.contains(&0))
+}
+
+/// Whether the file is bookkeeping stored next to the hashtables (caches, download validators,
+/// partially written files) rather than a hashtable
+pub fn is_hashtable_bookkeeping_file(path: impl AsRef<Path>) -> bool {
+    let path = path.as_ref();
+    is_hashtable_cache_file(path)
+        || is_partial_file(path)
+        || path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.ends_with(DOWNLOAD_METADATA_SUFFIX))
+}
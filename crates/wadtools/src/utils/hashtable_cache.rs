@@ -0,0 +1,286 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{self, eyre};
+use eyre::Context;
+use memmap2::Mmap;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use super::{read_hashtable_entries, PartialFile};
+
+/// Suffix appended to a text hashtable's file name to get the path of its cache
+pub const HASHTABLE_CACHE_SUFFIX: &str = ".wadtools-cache";
+
+const HASHTABLE_CACHE_MAGIC: &[u8; 4] = b"WTHC";
+const HASHTABLE_CACHE_VERSION: u32 = 2;
+/// magic, version, source size, source mtime, malformed line count, entry count, string pool length
+const HEADER_SIZE: usize = 4 + 4 + 8 + 8 + 8 + 8 + 8;
+
+/// A memory-mapped binary copy of a text wad hashtable.
+///
+/// Layout (little endian): header, `count` sorted `u64` hashes, `count + 1` `u32` offsets into
+/// the string pool, string pool. The header stores the size and mtime of the text file the cache
+/// was built from, so it can be rebuilt when the text file changes, and the number of malformed
+/// lines that were skipped while building it.
+#[derive(Debug)]
+pub struct HashtableCache {
+    mmap: Mmap,
+    count: usize,
+    pool_offset: usize,
+}
+
+/// Size and modification time of the text hashtable a cache was built from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SourceFingerprint {
+    size: u64,
+    mtime: u64,
+}
+
+impl SourceFingerprint {
+    fn of(path: &Utf8Path) -> eyre::Result<Self> {
+        let metadata = fs::metadata(path.as_std_path())?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos() as u64);
+
+        Ok(Self {
+            size: metadata.len(),
+            mtime,
+        })
+    }
+}
+
+impl HashtableCache {
+    pub fn cache_path(source_path: &Utf8Path) -> Utf8PathBuf {
+        Utf8PathBuf::from(format!("{}{}", source_path, HASHTABLE_CACHE_SUFFIX))
+    }
+
+    /// Opens the cache of a text hashtable, (re)building it first if it is missing or stale.
+    /// With `strict`, a cache that skipped malformed lines is rebuilt, which fails on them.
+    pub fn open_or_build(source_path: &Utf8Path, strict: bool) -> eyre::Result<Self> {
+        let fingerprint = SourceFingerprint::of(source_path)?;
+        let cache_path = Self::cache_path(source_path);
+
+        if let Some(cache) = Self::open(&cache_path, fingerprint, strict)? {
+            return Ok(cache);
+        }
+
+        tracing::info!("building hashtable cache: {}", cache_path);
+        Self::build(source_path, &cache_path, fingerprint, strict)?;
+        Self::open(&cache_path, fingerprint, strict)?
+            .ok_or_else(|| eyre!("failed to open freshly built cache: {}", cache_path))
+    }
+
+    /// Opens the cache of a text hashtable only if it exists and is up to date
+    pub fn open_if_fresh(source_path: &Utf8Path, strict: bool) -> eyre::Result<Option<Self>> {
+        let fingerprint = SourceFingerprint::of(source_path)?;
+        Self::open(&Self::cache_path(source_path), fingerprint, strict)
+    }
+
+    /// Opens the cache, returns `None` if it doesn't exist, doesn't match the fingerprint or
+    /// skipped malformed lines in `strict` mode
+    fn open(
+        cache_path: &Utf8Path,
+        fingerprint: SourceFingerprint,
+        strict: bool,
+    ) -> eyre::Result<Option<Self>> {
+        let file = match File::open(cache_path.as_std_path()) {
+            Ok(file) => file,
+            Err(_) => return Ok(None),
+        };
+        // SAFETY: the cache is only ever replaced atomically by renaming a new file over it,
+        // so the mapped file is never modified while we read it
+        let mmap = unsafe { Mmap::map(&file) }
+            .wrap_err(format!("failed to map hashtable cache: {}", cache_path))?;
+
+        if mmap.len() < HEADER_SIZE || &mmap[0..4] != HASHTABLE_CACHE_MAGIC {
+            return Ok(None);
+        }
+        let read_u64 =
+            |offset: usize| u64::from_le_bytes(mmap[offset..offset + 8].try_into().unwrap());
+        let version = u32::from_le_bytes(mmap[4..8].try_into().unwrap());
+        let cached_fingerprint = SourceFingerprint {
+            size: read_u64(8),
+            mtime: read_u64(16),
+        };
+        if version != HASHTABLE_CACHE_VERSION || cached_fingerprint != fingerprint {
+            return Ok(None);
+        }
+        let malformed_count = read_u64(24);
+        if strict && malformed_count > 0 {
+            return Ok(None);
+        }
+
+        // A corrupted header must not overflow, the cache is rebuilt instead
+        let count = usize::try_from(read_u64(32)).ok();
+        let pool_len = usize::try_from(read_u64(40)).ok();
+        let Some((count, pool_offset)) = count.and_then(|count| {
+            let pool_offset = count
+                .checked_mul(8)?
+                .checked_add(count.checked_add(1)?.checked_mul(4)?)?
+                .checked_add(HEADER_SIZE)?;
+            Some((count, pool_offset))
+        }) else {
+            return Ok(None);
+        };
+        let file_len = pool_len.and_then(|pool_len| pool_offset.checked_add(pool_len));
+        if file_len != Some(mmap.len()) {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            mmap,
+            count,
+            pool_offset,
+        }))
+    }
+
+    fn build(
+        source_path: &Utf8Path,
+        cache_path: &Utf8Path,
+        fingerprint: SourceFingerprint,
+        strict: bool,
+    ) -> eyre::Result<()> {
+        let (mut entries, malformed_count) = read_hashtable_entries(source_path, strict)?;
+        // Later lines win, like when inserting into a map
+        entries.reverse();
+        entries.sort_by_key(|(hash, _)| *hash);
+        entries.dedup_by_key(|(hash, _)| *hash);
+
+        let pool_len = entries.iter().map(|(_, path)| path.len()).sum::<usize>();
+        if pool_len > u32::MAX as usize {
+            return Err(eyre!("hashtable is too large to cache: {}", source_path));
+        }
+
+        // Write next to the cache and swap it in, so a running process never sees a partial file
+        let mut cache_file = PartialFile::create(cache_path)?;
+        let mut writer = BufWriter::new(cache_file.file());
+        writer.write_all(HASHTABLE_CACHE_MAGIC)?;
+        writer.write_all(&HASHTABLE_CACHE_VERSION.to_le_bytes())?;
+        writer.write_all(&fingerprint.size.to_le_bytes())?;
+        writer.write_all(&fingerprint.mtime.to_le_bytes())?;
+        writer.write_all(&(malformed_count as u64).to_le_bytes())?;
+        writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+        writer.write_all(&(pool_len as u64).to_le_bytes())?;
+        for (hash, _) in &entries {
+            writer.write_all(&hash.to_le_bytes())?;
+        }
+        let mut offset = 0u32;
+        writer.write_all(&offset.to_le_bytes())?;
+        for (_, path) in &entries {
+            offset += path.len() as u32;
+            writer.write_all(&offset.to_le_bytes())?;
+        }
+        for (_, path) in &entries {
+            writer.write_all(path.as_bytes())?;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        cache_file
+            .commit()
+            .wrap_err(format!("failed to write hashtable cache: {}", cache_path))?;
+
+        Ok(())
+    }
+
+    pub fn resolve(&self, hash: u64) -> Option<&str> {
+        let (mut low, mut high) = (0, self.count);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match self.hash_at(middle).cmp(&hash) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => return self.path_at(middle),
+            }
+        }
+
+        None
+    }
+
+    fn hash_at(&self, index: usize) -> u64 {
+        let offset = HEADER_SIZE + index * 8;
+        u64::from_le_bytes(self.mmap[offset..offset + 8].try_into().unwrap())
+    }
+
+    fn path_at(&self, index: usize) -> Option<&str> {
+        let offsets_start = HEADER_SIZE + self.count * 8;
+        let read_offset = |index: usize| {
+            let offset = offsets_start + index * 4;
+            u32::from_le_bytes(self.mmap[offset..offset + 4].try_into().unwrap()) as usize
+        };
+
+        let start = self.pool_offset + read_offset(index);
+        let end = self.pool_offset + read_offset(index + 1);
+        std::str::from_utf8(self.mmap.get(start..end)?).ok()
+    }
+}
+
+/// Whether the file is a binary hashtable cache rather than a text hashtable
+pub fn is_hashtable_cache_file(path: impl AsRef<Path>) -> bool {
+    path.as_ref()
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(HASHTABLE_CACHE_SUFFIX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_hashtable(dir: &Utf8Path, lines: &str) -> Utf8PathBuf {
+        let path = dir.join("hashes.game.txt");
+        fs::write(&path, lines).unwrap();
+        path
+    }
+
+    #[test]
+    fn build_open_resolve_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = Utf8Path::from_path(temp_dir.path()).unwrap();
+        let source_path = write_hashtable(
+            dir,
+            "00000000000000ff assets/last.dds\n\
+             0000000000000001 data/first.bin\n\
+             0000000000000010 data/shadowed.bin\n\
+             0000000000000010 data/later lines win.bin\n",
+        );
+
+        let cache = HashtableCache::open_or_build(&source_path, false).unwrap();
+        assert_eq!(cache.resolve(0x01), Some("data/first.bin"));
+        assert_eq!(cache.resolve(0x10), Some("data/later lines win.bin"));
+        assert_eq!(cache.resolve(0xff), Some("assets/last.dds"));
+        assert_eq!(cache.resolve(0x02), None);
+        assert_eq!(cache.resolve(u64::MAX), None);
+
+        let reopened = HashtableCache::open_if_fresh(&source_path, false)
+            .unwrap()
+            .expect("cache should be fresh");
+        assert_eq!(reopened.resolve(0xff), Some("assets/last.dds"));
+    }
+
+    #[test]
+    fn corrupted_header_is_rebuilt() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = Utf8Path::from_path(temp_dir.path()).unwrap();
+        let source_path = write_hashtable(dir, "0000000000000001 data/first.bin\n");
+        let cache_path = HashtableCache::cache_path(&source_path);
+        drop(HashtableCache::open_or_build(&source_path, false).unwrap());
+
+        for (offset, value) in [(32, u64::MAX), (32, u64::MAX / 8), (40, u64::MAX)] {
+            let mut data = fs::read(&cache_path).unwrap();
+            data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+            fs::write(&cache_path, &data).unwrap();
+
+            assert!(HashtableCache::open_if_fresh(&source_path, false)
+                .unwrap()
+                .is_none());
+            let cache = HashtableCache::open_or_build(&source_path, false).unwrap();
+            assert_eq!(cache.resolve(0x01), Some("data/first.bin"));
+        }
+    }
+}
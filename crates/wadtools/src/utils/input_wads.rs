@@ -0,0 +1,70 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{self, eyre, Context};
+use std::collections::HashSet;
+use walkdir::WalkDir;
+
+use super::is_wad_path;
+
+/// A wad file found through one of the inputs of a command
+#[derive(Debug, Clone)]
+pub struct InputWad {
+    pub path: Utf8PathBuf,
+    /// Path relative to the directory it was found in, or just the file name
+    pub relative_path: Utf8PathBuf,
+}
+
+/// Expands the inputs into wad files. Directories are searched recursively and inputs that
+/// are not existing paths are treated as glob patterns.
+pub fn collect_input_wads(inputs: &[String]) -> eyre::Result<Vec<InputWad>> {
+    let mut wads = Vec::<InputWad>::new();
+    let mut seen = HashSet::<Utf8PathBuf>::new();
+    let mut add_wad = |path: Utf8PathBuf, relative_path: Utf8PathBuf| {
+        if seen.insert(path.clone()) {
+            wads.push(InputWad {
+                path,
+                relative_path,
+            });
+        }
+    };
+
+    for input in inputs {
+        let input_path = Utf8Path::new(input);
+        if input_path.is_dir() {
+            for entry in WalkDir::new(input_path.as_std_path()).sort_by_file_name() {
+                let entry = entry.wrap_err(format!("failed to walk directory: {}", input))?;
+                let Ok(path) = Utf8PathBuf::from_path_buf(entry.into_path()) else {
+                    continue;
+                };
+                if !path.is_file() || !is_wad_path(&path) {
+                    continue;
+                }
+
+                let relative_path = path.strip_prefix(input_path)?.to_path_buf();
+                add_wad(path, relative_path);
+            }
+        } else if input_path.exists() {
+            let relative_path = Utf8PathBuf::from(input_path.file_name().unwrap_or_default());
+            add_wad(input_path.to_path_buf(), relative_path);
+        } else {
+            let mut matches = glob::glob(input)
+                .wrap_err(format!("invalid input pattern: {}", input))?
+                .collect::<Result<Vec<_>, _>>()?;
+            if matches.is_empty() {
+                return Err(eyre!("Input not found: {}", input));
+            }
+            matches.sort();
+
+            for path in matches {
+                let Ok(path) = Utf8PathBuf::from_path_buf(path) else {
+                    continue;
+                };
+                if path.is_file() {
+                    let relative_path = Utf8PathBuf::from(path.file_name().unwrap_or_default());
+                    add_wad(path, relative_path);
+                }
+            }
+        }
+    }
+
+    Ok(wads)
+}
@@ -1,10 +1,21 @@
+mod bin_hashtable;
 pub mod config;
 mod hashtable;
+mod hashtable_cache;
+mod input_wads;
+mod partial_file;
+mod wad_writer;
 
 use camino::{Utf8Path, Utf8PathBuf};
 use fancy_regex::Regex;
+use xxhash_rust::xxh64::xxh64;
 
+pub use bin_hashtable::*;
 pub use hashtable::*;
+pub use hashtable_cache::*;
+pub use input_wads::*;
+pub use partial_file::*;
+pub use wad_writer::*;
 
 /// Creates a filter pattern from an optional regex string.
 /// Defaults to case-insensitive matching unless the user explicitly sets (?i) or (?-i).
@@ -26,6 +37,42 @@ pub fn format_chunk_path_hash(path_hash: u64) -> String {
     format!("{:016x}", path_hash)
 }
 
+/// Lowercases and uses forward slashes, like the game does before hashing a chunk path
+pub fn normalize_chunk_path(path: &str) -> String {
+    path.replace('\\', "/").to_lowercase()
+}
+
+/// Hashes a chunk path the way the game does (normalized, xxhash64)
+pub fn hash_chunk_path(path: &str) -> u64 {
+    hash_normalized_chunk_path(&normalize_chunk_path(path))
+}
+
+/// Hashes a chunk path that went through [`normalize_chunk_path`] already, for hot loops that
+/// build many paths out of normalized parts
+pub fn hash_normalized_chunk_path(path: &str) -> u64 {
+    xxh64(path.as_bytes(), 0)
+}
+
+/// Parses a 16-hex path hash, or hashes the input as a chunk path otherwise
+pub fn parse_chunk_path_or_hash(path_or_hash: &str) -> u64 {
+    let is_hex = path_or_hash.len() == 16 && path_or_hash.chars().all(|c| c.is_ascii_hexdigit());
+    if is_hex {
+        if let Ok(path_hash) = u64::from_str_radix(path_or_hash, 16) {
+            return path_hash;
+        }
+    }
+
+    hash_chunk_path(path_or_hash)
+}
+
+/// Whether the file is a wad archive (`.wad`, `.wad.client` or `.wad.mobile`)
+pub fn is_wad_path(path: &Utf8Path) -> bool {
+    let file_name = path.file_name().unwrap_or("").to_lowercase();
+    file_name.ends_with(".wad")
+        || file_name.ends_with(".wad.client")
+        || file_name.ends_with(".wad.mobile")
+}
+
 pub fn is_hex_chunk_path(path: &Utf8Path) -> bool {
     let file_name = path.file_name().unwrap_or("");
     file_name.len() == 16 && file_name.chars().all(|c| c.is_ascii_hexdigit())
@@ -60,6 +107,12 @@ pub fn truncate_middle(input: &str, max_len: usize) -> String {
     format!("{}...{}", left_str, right_str)
 }
 
+/// Returns the directory for data that can be rebuilt at any time, like detected chunk kinds
+pub fn default_cache_dir() -> Option<Utf8PathBuf> {
+    let proj = directories_next::ProjectDirs::from("io", "LeagueToolkit", "wadtools")?;
+    Utf8PathBuf::from_path_buf(proj.cache_dir().to_path_buf()).ok()
+}
+
 /// Returns the default directory where wad hashtables should be looked up.
 /// On Windows, prefers the user's Documents folder: Documents/LeagueToolkit/wad_hashtables
 /// On other platforms, uses platform-appropriate data directory via directories_next.
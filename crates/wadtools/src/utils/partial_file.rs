@@ -0,0 +1,81 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{self, Context};
+use std::{
+    fs::{self, File},
+    path::Path,
+};
+
+/// Suffix of the file a [`PartialFile`] is written to before it replaces its target
+pub const PARTIAL_FILE_SUFFIX: &str = ".wadtools-partial";
+
+/// A file that is written next to its target and only renamed over it by [`PartialFile::commit`].
+///
+/// The partial file is removed when this is dropped without being committed, so a failed write
+/// leaves the target untouched and nothing else behind.
+#[derive(Debug)]
+pub struct PartialFile {
+    target_path: Utf8PathBuf,
+    partial_path: Utf8PathBuf,
+    file: Option<File>,
+}
+
+impl PartialFile {
+    pub fn create(target_path: impl AsRef<Utf8Path>) -> eyre::Result<Self> {
+        let target_path = target_path.as_ref().to_path_buf();
+        let partial_path = Utf8PathBuf::from(format!("{}{}", target_path, PARTIAL_FILE_SUFFIX));
+        let file = File::create(partial_path.as_std_path())
+            .wrap_err(format!("failed to create file: {}", partial_path))?;
+
+        Ok(Self {
+            target_path,
+            partial_path,
+            file: Some(file),
+        })
+    }
+
+    pub fn file(&mut self) -> &mut File {
+        self.file
+            .as_mut()
+            .expect("partial file is open until committed")
+    }
+
+    /// Path of the partial file, to read back what was written before committing
+    pub fn partial_path(&self) -> &Utf8Path {
+        &self.partial_path
+    }
+
+    /// Flushes the partial file to disk and replaces the target with it
+    pub fn commit(mut self) -> eyre::Result<Utf8PathBuf> {
+        self.file().sync_all()?;
+        // Close the file first, renaming an open file fails on Windows
+        self.file = None;
+
+        if let Err(error) = fs::rename(
+            self.partial_path.as_std_path(),
+            self.target_path.as_std_path(),
+        ) {
+            let _ = fs::remove_file(self.partial_path.as_std_path());
+            return Err(eyre::Report::new(error)
+                .wrap_err(format!("failed to replace {}", self.target_path)));
+        }
+
+        Ok(std::mem::take(&mut self.target_path))
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        // Committing takes the file, anything still open here was abandoned
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(self.partial_path.as_std_path());
+        }
+    }
+}
+
+/// Whether the file is a [`PartialFile`] left behind by an interrupted write
+pub fn is_partial_file(path: impl AsRef<Path>) -> bool {
+    path.as_ref()
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(PARTIAL_FILE_SUFFIX))
+}
@@ -0,0 +1,184 @@
+use color_eyre::eyre::{self, eyre};
+use flate2::{write::GzEncoder, Compression};
+use league_toolkit::wad::{WadChunk, WadChunkCompression};
+use std::{
+    collections::HashMap,
+    io::{Seek, SeekFrom, Write},
+};
+use xxhash_rust::xxh3::xxh3_64;
+
+const WAD_MAGIC: u16 = 0x5752;
+const WAD_VERSION_MAJOR: u8 = 3;
+const WAD_VERSION_MINOR: u8 = 4;
+const WAD_SIGNATURE_SIZE: usize = 256;
+const WAD_TOC_ENTRY_SIZE: usize = 32;
+const ZSTD_COMPRESSION_LEVEL: i32 = 3;
+
+/// Streams chunks into a WAD v3.4 file.
+///
+/// The header and a placeholder TOC are written up front, chunk data is appended as it comes in,
+/// and the real TOC (sorted by path hash, which the game requires) is written by [`WadWriter::finish`].
+pub struct WadWriter<W: Write + Seek> {
+    writer: W,
+    chunk_count: usize,
+    toc_offset: u64,
+    data_offset: u64,
+    chunks: Vec<WadChunk>,
+    /// Output data offsets of raw chunks, keyed by their data offset and size in the source wad
+    raw_data_offsets: HashMap<(usize, usize), usize>,
+}
+
+impl<W: Write + Seek> WadWriter<W> {
+    pub fn new(mut writer: W, chunk_count: usize) -> eyre::Result<Self> {
+        writer.write_all(&WAD_MAGIC.to_le_bytes())?;
+        writer.write_all(&[WAD_VERSION_MAJOR, WAD_VERSION_MINOR])?;
+        writer.write_all(&[0; WAD_SIGNATURE_SIZE])?;
+        writer.write_all(&0u64.to_le_bytes())?;
+        writer.write_all(&(chunk_count as u32).to_le_bytes())?;
+
+        let toc_offset = writer.stream_position()?;
+        let toc_size = chunk_count * WAD_TOC_ENTRY_SIZE;
+        writer.write_all(&vec![0; toc_size])?;
+
+        Ok(Self {
+            writer,
+            chunk_count,
+            toc_offset,
+            data_offset: toc_offset + toc_size as u64,
+            chunks: Vec::with_capacity(chunk_count),
+            raw_data_offsets: HashMap::new(),
+        })
+    }
+
+    /// Compresses `data` with `compression` and appends it as a new chunk
+    pub fn write_chunk(
+        &mut self,
+        path_hash: u64,
+        data: &[u8],
+        compression: WadChunkCompression,
+    ) -> eyre::Result<WadChunk> {
+        let compressed_data = compress_chunk_data(data, compression)?;
+        let data_offset = self.append_chunk_data(&compressed_data)?;
+
+        let chunk = WadChunk {
+            path_hash,
+            data_offset,
+            compressed_size: compressed_data.len(),
+            uncompressed_size: data.len(),
+            compression_type: compression,
+            is_duplicated: false,
+            frame_count: 0,
+            start_frame: 0,
+            checksum: xxh3_64(&compressed_data),
+        };
+        self.chunks.push(chunk);
+
+        Ok(chunk)
+    }
+
+    /// Appends already compressed chunk data verbatim, keeping the metadata of `chunk`.
+    ///
+    /// Chunks that share their data in the source wad share it in the output as well, so all
+    /// raw chunks of a writer must come from the same source wad.
+    pub fn write_raw_chunk(&mut self, chunk: &WadChunk, raw_data: &[u8]) -> eyre::Result<WadChunk> {
+        if raw_data.len() != chunk.compressed_size {
+            return Err(eyre!(
+                "raw chunk data size mismatch (path_hash: {:016x}, expected: {}, actual: {})",
+                chunk.path_hash,
+                chunk.compressed_size,
+                raw_data.len()
+            ));
+        }
+
+        let source_key = (chunk.data_offset, chunk.compressed_size);
+        let (data_offset, is_duplicated) = match self.raw_data_offsets.get(&source_key) {
+            Some(&data_offset) => {
+                self.check_chunk_count()?;
+                (data_offset, true)
+            }
+            None => {
+                let data_offset = self.append_chunk_data(raw_data)?;
+                self.raw_data_offsets.insert(source_key, data_offset);
+                (data_offset, false)
+            }
+        };
+        let chunk = WadChunk {
+            data_offset,
+            is_duplicated,
+            ..*chunk
+        };
+        self.chunks.push(chunk);
+
+        Ok(chunk)
+    }
+
+    /// Writes the TOC and returns the underlying writer
+    pub fn finish(mut self) -> eyre::Result<W> {
+        if self.chunks.len() != self.chunk_count {
+            return Err(eyre!(
+                "chunk count mismatch (expected: {}, written: {})",
+                self.chunk_count,
+                self.chunks.len()
+            ));
+        }
+
+        self.chunks.sort_by_key(|chunk| chunk.path_hash);
+        if let Some(pair) = self
+            .chunks
+            .windows(2)
+            .find(|pair| pair[0].path_hash == pair[1].path_hash)
+        {
+            return Err(eyre!(
+                "duplicate chunk (path_hash: {:016x})",
+                pair[0].path_hash
+            ));
+        }
+
+        self.writer.seek(SeekFrom::Start(self.toc_offset))?;
+        for chunk in &self.chunks {
+            chunk.write_v3_4(&mut self.writer)?;
+        }
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    fn check_chunk_count(&self) -> eyre::Result<()> {
+        if self.chunks.len() >= self.chunk_count {
+            return Err(eyre!(
+                "too many chunks written (expected: {})",
+                self.chunk_count
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn append_chunk_data(&mut self, data: &[u8]) -> eyre::Result<usize> {
+        self.check_chunk_count()?;
+
+        let data_offset = self.data_offset;
+        if data_offset > u32::MAX as u64 {
+            return Err(eyre!("wad file exceeds the 4 GB limit of the v3 format"));
+        }
+        self.writer.write_all(data)?;
+        self.data_offset += data.len() as u64;
+
+        Ok(data_offset as usize)
+    }
+}
+
+pub fn compress_chunk_data(data: &[u8], compression: WadChunkCompression) -> eyre::Result<Vec<u8>> {
+    match compression {
+        WadChunkCompression::None => Ok(data.to_vec()),
+        WadChunkCompression::GZip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        }
+        WadChunkCompression::Zstd => Ok(zstd::encode_all(data, ZSTD_COMPRESSION_LEVEL)?),
+        WadChunkCompression::Satellite | WadChunkCompression::ZstdMulti => {
+            Err(eyre!("unsupported chunk compression: {}", compression))
+        }
+    }
+}